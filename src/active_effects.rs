@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum EffectDuration {
+    RestOfPhase,
+    #[default]
+    RestOfTurn,
+    // Outlasts the current turn and expires when the turn after it ends
+    ThroughNextTurn,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActiveEffect<T> {
+    pub effect: T,
+    pub duration: EffectDuration,
+}
+
+// Effects that consequences leave on the game state, eg. actions that can't be
+// taken. Each one is dropped once its duration runs out
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActiveEffects<T> {
+    effects: Vec<ActiveEffect<T>>,
+}
+
+impl<T> Default for ActiveEffects<T> {
+    fn default() -> Self {
+        ActiveEffects { effects: vec![] }
+    }
+}
+
+impl<T> ActiveEffects<T> {
+    pub fn add(&mut self, effect: T, duration: EffectDuration) {
+        self.effects.push(ActiveEffect { effect, duration });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.effects
+            .iter()
+            .map(|active_effect| &active_effect.effect)
+    }
+
+    pub fn end_phase(&mut self) {
+        self.effects
+            .retain(|active_effect| active_effect.duration != EffectDuration::RestOfPhase);
+    }
+
+    pub fn end_turn(&mut self) {
+        self.effects
+            .retain(|active_effect| active_effect.duration == EffectDuration::ThroughNextTurn);
+
+        for active_effect in self.effects.iter_mut() {
+            active_effect.duration = EffectDuration::RestOfTurn;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effects(effects: &ActiveEffects<&'static str>) -> Vec<&'static str> {
+        effects.iter().copied().collect()
+    }
+
+    #[test]
+    fn effects_last_until_their_duration_runs_out() {
+        let mut active_effects = ActiveEffects::default();
+        active_effects.add("phase", EffectDuration::RestOfPhase);
+        active_effects.add("turn", EffectDuration::RestOfTurn);
+        active_effects.add("next turn", EffectDuration::ThroughNextTurn);
+
+        active_effects.end_phase();
+        assert_eq!(effects(&active_effects), vec!["turn", "next turn"]);
+
+        active_effects.end_turn();
+        assert_eq!(effects(&active_effects), vec!["next turn"]);
+
+        active_effects.end_turn();
+        assert!(effects(&active_effects).is_empty());
+    }
+
+    #[test]
+    fn through_next_turn_outlasts_the_phases_of_the_next_turn() {
+        let mut active_effects = ActiveEffects::default();
+        active_effects.add("next turn", EffectDuration::ThroughNextTurn);
+
+        active_effects.end_turn();
+        active_effects.end_phase();
+        assert_eq!(effects(&active_effects), vec!["next turn"]);
+    }
+}
@@ -1,63 +1,179 @@
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::Rng;
 use std::char;
 use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Deck {
     pub cards: Vec<Card>,
 }
 
 impl Deck {
-    pub fn shuffle(mut self) -> Self {
-        self.cards.shuffle(&mut thread_rng());
-        return self;
+    pub fn shuffle<R: Rng>(&mut self, rng: &mut R) {
+        self.cards.shuffle(rng);
     }
 }
 
-impl Default for Deck {
-    fn default() -> Self {
+impl Deck {
+    pub fn from_specification(deck_specification: &DeckSpecification) -> Self {
         let mut deck = Deck { cards: vec![] };
-        for rank in 2..15 {
-            deck.cards.push(Card {
-                suit: Suit::Club,
-                rank: CardRank::from_usize(rank),
-            });
-            deck.cards.push(Card {
-                suit: Suit::Diamond,
-                rank: CardRank::from_usize(rank),
-            });
-            deck.cards.push(Card {
-                suit: Suit::Heart,
-                rank: CardRank::from_usize(rank),
-            });
-            deck.cards.push(Card {
-                suit: Suit::Spade,
-                rank: CardRank::from_usize(rank),
-            });
+
+        match deck_specification {
+            DeckSpecification::Standard {
+                count,
+                ranks,
+                jokers,
+            } => {
+                for _ in 0..*count {
+                    for rank in ranks.ranks() {
+                        for suit in Suit::all() {
+                            deck.cards.push(Card::new(suit, rank.clone()));
+                        }
+                    }
+
+                    for joker_color in jokers.iter() {
+                        deck.cards.push(Card::joker(joker_color.clone()));
+                    }
+                }
+            }
+            DeckSpecification::Cards(cards) => deck.cards = cards.clone(),
         }
 
         return deck;
     }
 }
 
+impl Default for Deck {
+    fn default() -> Self {
+        Deck::from_specification(&Default::default())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum DeckRanks {
+    #[default]
+    All,
+    // Sevens through aces, 32 cards per deck
+    Piquet,
+    // Nines through aces, 24 cards per deck
+    Euchre,
+    Only(Vec<CardRank>),
+}
+
+impl DeckRanks {
+    pub fn ranks(&self) -> Vec<CardRank> {
+        match self {
+            DeckRanks::All => CardRank::all(),
+            DeckRanks::Piquet => (7..15).map(CardRank::from_usize).collect(),
+            DeckRanks::Euchre => (9..15).map(CardRank::from_usize).collect(),
+            DeckRanks::Only(ranks) => ranks.clone(),
+        }
+    }
+}
+
+fn one_deck() -> usize {
+    1
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeckSpecification {
+    // Some number of standard decks, every suit of each rank, shuffled together
+    Standard {
+        #[serde(default = "one_deck")]
+        count: usize,
+
+        #[serde(default)]
+        ranks: DeckRanks,
+
+        // Jokers added to each deck
+        #[serde(default)]
+        jokers: Vec<JokerColor>,
+    },
+    // Exactly these cards
+    Cards(Vec<Card>),
+}
+
+impl Default for DeckSpecification {
+    fn default() -> Self {
+        DeckSpecification::Standard {
+            count: one_deck(),
+            ranks: Default::default(),
+            jokers: vec![],
+        }
+    }
+}
+
+impl DeckSpecification {
+    pub fn card_count(&self) -> usize {
+        match self {
+            DeckSpecification::Standard {
+                count,
+                ranks,
+                jokers,
+            } => count * (ranks.ranks().len() * Suit::all().len() + jokers.len()),
+            DeckSpecification::Cards(cards) => cards.len(),
+        }
+    }
+
+    pub fn ranks(&self) -> Vec<CardRank> {
+        match self {
+            DeckSpecification::Standard { ranks, jokers, .. } => {
+                let mut ranks = ranks.ranks();
+                if !jokers.is_empty() {
+                    ranks.push(CardRank::Joker);
+                }
+
+                ranks
+            }
+            DeckSpecification::Cards(cards) => {
+                let mut ranks: Vec<CardRank> = vec![];
+                for card in cards.iter() {
+                    if !ranks.contains(&card.rank) {
+                        ranks.push(card.rank.clone());
+                    }
+                }
+
+                ranks
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Suit {
     Club,
     Diamond,
     Heart,
     Spade,
+    // Jokers don't have a suit, only a color. Joker cards always have the
+    // joker rank
+    Joker(JokerColor),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JokerColor {
+    Red,
+    Black,
+    White,
 }
 
 impl Suit {
+    pub fn all() -> Vec<Suit> {
+        vec![Suit::Club, Suit::Diamond, Suit::Heart, Suit::Spade]
+    }
+
     pub fn unicode_code_point(&self) -> u32 {
         match self {
             Suit::Spade => 0xA0,
             Suit::Heart => 0xB0,
             Suit::Diamond => 0xC0,
             Suit::Club => 0xD0,
+            // The jokers sit at the end of the heart, diamond and club rows
+            Suit::Joker(JokerColor::Red) => 0xB0,
+            Suit::Joker(JokerColor::Black) => 0xC0,
+            Suit::Joker(JokerColor::White) => 0xD0,
         }
     }
 }
@@ -68,7 +184,7 @@ pub enum CardValue {
     Numeric(usize),
 }
 
-#[derive(Clone, Debug, PartialOrd, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CardRank {
     Two,
     Three,
@@ -83,28 +199,38 @@ pub enum CardRank {
     Queen,
     King,
     Ace,
+    Joker,
 }
 
 impl CardRank {
     pub fn from_usize(rank: usize) -> CardRank {
+        CardRank::try_from_usize(rank).unwrap_or_else(|| panic!("Unknown rank: {}", rank))
+    }
+
+    pub fn try_from_usize(rank: usize) -> Option<CardRank> {
         match rank {
-            2 => CardRank::Two,
-            3 => CardRank::Three,
-            4 => CardRank::Four,
-            5 => CardRank::Five,
-            6 => CardRank::Six,
-            7 => CardRank::Seven,
-            8 => CardRank::Eight,
-            9 => CardRank::Nine,
-            10 => CardRank::Ten,
-            11 => CardRank::Jack,
-            12 => CardRank::Queen,
-            13 => CardRank::King,
-            14 => CardRank::Ace,
-            _ => panic!("Unknown rank: {}", rank),
+            2 => Some(CardRank::Two),
+            3 => Some(CardRank::Three),
+            4 => Some(CardRank::Four),
+            5 => Some(CardRank::Five),
+            6 => Some(CardRank::Six),
+            7 => Some(CardRank::Seven),
+            8 => Some(CardRank::Eight),
+            9 => Some(CardRank::Nine),
+            10 => Some(CardRank::Ten),
+            11 => Some(CardRank::Jack),
+            12 => Some(CardRank::Queen),
+            13 => Some(CardRank::King),
+            14 => Some(CardRank::Ace),
+            _ => None,
         }
     }
 
+    // The ranks of a standard deck, without jokers
+    pub fn all() -> Vec<CardRank> {
+        (2..15).map(CardRank::from_usize).collect()
+    }
+
     pub fn unicode_code_point(&self) -> u32 {
         match self {
             CardRank::Two => 0x2,
@@ -120,14 +246,49 @@ impl CardRank {
             CardRank::Queen => 0xD,
             CardRank::King => 0xE,
             CardRank::Ace => 0x1,
+            CardRank::Joker => 0xF,
         }
     }
 }
 
-#[derive(Clone, PartialEq, Serialize, Deserialize)]
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Card {
     pub suit: Suit,
     pub rank: CardRank,
+    // Whether every player has seen the card, eg. as it was played onto a
+    // pile. Everyone still knows what it is once it's covered up or picked up
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub public: bool,
+}
+
+// Cards are the same card whoever has seen them
+impl PartialEq for Card {
+    fn eq(&self, other: &Self) -> bool {
+        self.suit == other.suit && self.rank == other.rank
+    }
+}
+
+impl Card {
+    pub fn new(suit: Suit, rank: CardRank) -> Self {
+        Card {
+            suit,
+            rank,
+            public: false,
+        }
+    }
+
+    pub fn joker(color: JokerColor) -> Self {
+        Card::new(Suit::Joker(color), CardRank::Joker)
+    }
+
+    // Jokers must have both the joker suit and rank, other cards neither
+    pub fn valid(&self) -> bool {
+        matches!(self.suit, Suit::Joker(_)) == (self.rank == CardRank::Joker)
+    }
 }
 
 impl fmt::Debug for Card {
@@ -148,11 +309,10 @@ impl fmt::Display for Card {
                     + self.suit.unicode_code_point()
                     + self.rank.unicode_code_point()
             )
-            .expect(&format!(
+            .unwrap_or_else(|| panic!(
                 "Invalid card to unicode conversion for: {:#?} {:#?}",
                 self.rank, self.suit
-            ))
-            .to_string(),
+            )),
         )
     }
 }
@@ -175,6 +335,20 @@ pub struct CardGroup {
 }
 
 impl CardGroup {
+    // Puts cards on top of the card group. Cards put into a group that shows
+    // them are seen by everyone, even if they're covered up straight away
+    pub fn add_cards(&mut self, cards: impl IntoIterator<Item = Card>) {
+        let seen_going_in = matches!(
+            self.visibility,
+            CardGroupVisibility::FaceUp | CardGroupVisibility::TopFaceUpRestFaceDown
+        );
+
+        self.cards.extend(cards.into_iter().map(|mut card| {
+            card.public |= seen_going_in;
+            card
+        }));
+    }
+
     pub fn at_or_over_initial_deal_size(&self) -> Option<bool> {
         if let Some(initial_deal_count) = self.initial_deal_count {
             if self.cards.len() >= initial_deal_count {
@@ -196,31 +370,54 @@ impl fmt::Debug for CardGroup {
 
 const PLAYING_CARD_BACK: &str = "🂠 ";
 
-// TODO: Fix visible only to owner once users have different outputs
+impl CardGroup {
+    // The cards a player can see in this card group, in order, with None for
+    // each card they can't. VisibleToOwner groups are only face up for
+    // whoever holds them
+    pub fn visible_cards(&self, seen_by_owner: bool) -> Vec<Option<&Card>> {
+        let top_card_index = self.cards.len().checked_sub(1);
+
+        return self
+            .cards
+            .iter()
+            .enumerate()
+            .map(|(card_index, card)| {
+                let face_up = match self.visibility {
+                    CardGroupVisibility::FaceDown => false,
+                    CardGroupVisibility::FaceUp => true,
+                    CardGroupVisibility::VisibleToOwner => seen_by_owner,
+                    // The top of a card group is the end of its card list
+                    CardGroupVisibility::TopFaceUpRestFaceDown => {
+                        Some(card_index) == top_card_index
+                    }
+                };
+
+                face_up.then_some(card)
+            })
+            .collect();
+    }
+
+    // What a player sees of this card group
+    pub fn view(&self, seen_by_owner: bool) -> String {
+        return view_cards(self.visible_cards(seen_by_owner));
+    }
+}
+
+// Shows visible cards face up and the rest as card backs
+pub fn view_cards<'a>(visible_cards: impl IntoIterator<Item = Option<&'a Card>>) -> String {
+    return visible_cards
+        .into_iter()
+        .map(|visible_card| match visible_card {
+            Some(card) => card.to_string(),
+            None => PLAYING_CARD_BACK.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+}
+
+// Shows the card group the way anyone but its owner would see it
 impl fmt::Display for CardGroup {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let string_repr = match self.visibility {
-            CardGroupVisibility::FaceDown => self
-                .cards
-                .iter()
-                .map(|_| PLAYING_CARD_BACK.to_string())
-                .collect::<Vec<String>>()
-                .join(", "),
-            CardGroupVisibility::FaceUp | CardGroupVisibility::VisibleToOwner => self
-                .cards
-                .iter()
-                .map(|c| c.to_string())
-                .collect::<Vec<String>>()
-                .join(", "),
-            CardGroupVisibility::TopFaceUpRestFaceDown => {
-                if self.cards.len() == 0 {
-                    "".to_string()
-                } else {
-                    self.cards[0].to_string()
-                }
-            }
-        };
-
-        write!(f, "{}", string_repr)
+        write!(f, "{}", self.view(false))
     }
 }
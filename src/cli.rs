@@ -0,0 +1,271 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+const DEFAULT_RULES_PATH: &str = "poo_head_rules.yaml";
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+#[derive(Debug, Parser)]
+#[command(about = "Plays card games described by a rules file")]
+pub struct Cli {
+    /// How results are printed. Prompts during a game are always text
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    // Playing with the default rules when no command is given
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start a new game
+    Play(PlayArgs),
+    /// Check a rules file for problems without playing
+    Validate {
+        #[arg(default_value = DEFAULT_RULES_PATH)]
+        rules_path: PathBuf,
+    },
+    /// Pick a saved game back up
+    Resume(ResumeArgs),
+    /// Play a logged game again and check it ends the same way
+    Replay {
+        log_path: PathBuf,
+
+        #[arg(default_value = DEFAULT_RULES_PATH)]
+        rules_path: PathBuf,
+    },
+    /// Play many games between bots and report how they went
+    Simulate(SimulateArgs),
+    /// Host a game that people join from other terminals with connect
+    Serve(ServeArgs),
+    /// Join a game hosted with serve
+    Connect {
+        #[arg(default_value = DEFAULT_ADDRESS)]
+        address: String,
+
+        /// Takes the seat with this name. The first free seat is taken when
+        /// none is given
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Takes back the seat this token was given for, after losing the
+        /// connection
+        #[arg(long)]
+        seat_token: Option<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct PlayArgs {
+    #[arg(default_value = DEFAULT_RULES_PATH)]
+    pub rules_path: PathBuf,
+
+    /// A player's name, once per player in seating order. NAME:random seats a
+    /// bot that plays at random and NAME:heuristic a bot that plays Poo Head
+    /// sensibly, optionally as NAME:heuristic:easy, medium or hard.
+    /// NAME:mcts seats a tree search bot for any rules, optionally with a
+    /// number of playouts or a time per decision, eg. NAME:mcts:250ms. Players
+    /// are asked for when none are given
+    #[arg(short, long = "player")]
+    pub players: Vec<String>,
+
+    #[command(flatten)]
+    pub new_game: NewGameArgs,
+
+    /// Saves the game here before each decision, as JSON if the path ends in
+    /// .json and YAML otherwise
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub house_rules: HouseRulesArgs,
+
+    #[command(flatten)]
+    pub terminal: TerminalArgs,
+}
+
+impl Default for PlayArgs {
+    fn default() -> Self {
+        PlayArgs {
+            rules_path: DEFAULT_RULES_PATH.into(),
+            players: vec![],
+            new_game: Default::default(),
+            save: None,
+            house_rules: Default::default(),
+            terminal: Default::default(),
+        }
+    }
+}
+
+// Options for any command that starts a new game
+#[derive(Debug, Default, Args)]
+pub struct NewGameArgs {
+    /// Seeds the shuffle. A random seed is used when none is given
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Logs the seed and every action and choice here as they happen, one
+    /// JSON object per line, for replaying the game
+    #[arg(long)]
+    pub log: Option<PathBuf>,
+}
+
+// How the table plays, for any command that plays a game, new or resumed
+#[derive(Debug, Default, Args)]
+pub struct HouseRulesArgs {
+    /// Lets players take back actions until their turn passes
+    #[arg(long)]
+    pub undo: bool,
+}
+
+// Options for people playing at this terminal
+#[derive(Debug, Default, Args)]
+pub struct TerminalArgs {
+    /// Clears the screen and waits for the next player between turns, for
+    /// players sharing one terminal
+    #[arg(long)]
+    pub hot_seat: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ResumeArgs {
+    pub snapshot_path: PathBuf,
+
+    #[arg(default_value = DEFAULT_RULES_PATH)]
+    pub rules_path: PathBuf,
+
+    /// Saves the game here instead of back to the snapshot it was resumed from
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub house_rules: HouseRulesArgs,
+
+    #[command(flatten)]
+    pub terminal: TerminalArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct SimulateArgs {
+    #[arg(default_value = DEFAULT_RULES_PATH)]
+    pub rules_path: PathBuf,
+
+    /// A bot's seat as NAME:KIND, eg. A:random or B:heuristic:hard, once per
+    /// player in seating order
+    #[arg(short, long = "player", required = true)]
+    pub players: Vec<String>,
+
+    /// How many games to play
+    #[arg(short = 'n', long, default_value_t = 100)]
+    pub games: usize,
+
+    /// Seeds the first game, and each game after it the next seed. A random
+    /// seed is used when none is given
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Games still going after this many turns are stopped and counted as
+    /// stalled
+    #[arg(long, default_value_t = 1000)]
+    pub turn_limit: usize,
+
+    /// Games whose players have been asked to decide this many times are
+    /// stopped and counted as stalled, even part way through a turn
+    #[arg(long, default_value_t = 20000)]
+    pub decision_limit: usize,
+
+    /// How many games to play at once. Defaults to the number of cores
+    #[arg(long)]
+    pub threads: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    #[arg(default_value = DEFAULT_RULES_PATH)]
+    pub rules_path: PathBuf,
+
+    /// A player's name, once per player in seating order. People join NAME
+    /// seats with connect. NAME:KIND seats a bot that the server plays, the
+    /// same kinds as play takes
+    #[arg(short, long = "player", required = true)]
+    pub players: Vec<String>,
+
+    /// Where to listen for players. Port 0 picks a free port
+    #[arg(long, default_value = DEFAULT_ADDRESS)]
+    pub bind: String,
+
+    #[command(flatten)]
+    pub new_game: NewGameArgs,
+
+    #[command(flatten)]
+    pub house_rules: HouseRulesArgs,
+
+    /// How many seconds to wait for a player who lost their connection
+    /// before the fallback bot plays for them
+    #[arg(long, default_value_t = 60)]
+    pub grace_period: u64,
+
+    /// How many seconds a player has to make each decision before their
+    /// connection is treated as lost, so a connection that died without
+    /// closing can't hold the game up forever
+    #[arg(long, default_value_t = 300)]
+    pub decision_timeout: u64,
+
+    /// A bot to play for anyone who doesn't come back within the grace
+    /// period, eg. random or heuristic:hard. They get their seat back when
+    /// they rejoin. Without one the game waits for them
+    #[arg(long)]
+    pub fallback_bot: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn arguments_are_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn shared_arguments_parse_for_each_command() {
+        let cli = Cli::try_parse_from([
+            "card-game",
+            "serve",
+            "-p",
+            "Ann",
+            "--seed",
+            "3",
+            "--log",
+            "game.jsonl",
+            "--undo",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Serve(serve_args)) => {
+                assert_eq!(serve_args.new_game.seed, Some(3));
+                assert_eq!(serve_args.new_game.log, Some("game.jsonl".into()));
+                assert!(serve_args.house_rules.undo);
+            }
+            command => panic!("Expected serve, found: {:?}", command),
+        }
+
+        let cli = Cli::try_parse_from(["card-game", "resume", "game.yaml", "--hot-seat"]).unwrap();
+        match cli.command {
+            Some(Command::Resume(resume_args)) => {
+                assert!(resume_args.terminal.hot_seat);
+                assert!(!resume_args.house_rules.undo);
+            }
+            command => panic!("Expected resume, found: {:?}", command),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::card_deck::{Card, CardRank};
+
+// Problems with how the game rules are written. Retrying won't help, the rules
+// file needs fixing
+#[derive(Clone, Debug, PartialEq)]
+pub enum RulesError {
+    EmptyDeck,
+    IncompleteCardGroupId,
+    InvalidCard(Card),
+    InvalidPlayerCounts {
+        min_player_count: usize,
+        max_player_count: usize,
+    },
+    JokerInDeckRanks,
+    MissingCardDescription(CardRank),
+    UnknownActionDescription(String),
+    UnknownCardGroup {
+        name: String,
+        available: Vec<String>,
+    },
+    UnknownCardGroupOwner(String),
+    UnknownRank(usize),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::EmptyDeck => write!(f, "The deck specification has no cards in it"),
+            RulesError::IncompleteCardGroupId => write!(
+                f,
+                "Invalid card group identifier. Neither name nor 'first_with_cards_of' set"
+            ),
+            RulesError::InvalidCard(card) => write!(
+                f,
+                "Jokers need both the Joker suit and rank, other cards can't have either. Given: {:?} of {:?}",
+                card.rank, card.suit,
+            ),
+            RulesError::InvalidPlayerCounts {
+                min_player_count,
+                max_player_count,
+            } => write!(
+                f,
+                "Player counts must be at least 1 and the minimum can't be over the maximum. Given: min {}, max {}",
+                min_player_count, max_player_count,
+            ),
+            RulesError::JokerInDeckRanks => write!(
+                f,
+                "Standard decks have every rank in each suit, and jokers have no suit. Add them with jokers instead"
+            ),
+            RulesError::MissingCardDescription(rank) => {
+                write!(f, "No card description in the game rules for: {:?}", rank)
+            }
+            RulesError::UnknownActionDescription(action_description) => write!(
+                f,
+                "No action has the description: {}",
+                action_description
+            ),
+            RulesError::UnknownCardGroup { name, available } => write!(
+                f,
+                "Card group name doesn't match anything. Given: {}. Available: {}",
+                name,
+                available.join(", "),
+            ),
+            RulesError::UnknownCardGroupOwner(owner_name) => write!(
+                f,
+                "Invalid card group owner name. Right now only 'communal_cards' is supported. Given: {}",
+                owner_name,
+            ),
+            RulesError::UnknownRank(rank) => write!(
+                f,
+                "Unknown card rank: {}. Ranks go from 2 to 14, with 11 to 14 for jack, queen, king and ace",
+                rank
+            ),
+        }
+    }
+}
+
+// A problem found by checking the game rules before a game starts. The path
+// points at where in the rules file it is, eg. game_flow[1].turn_phases[0]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RulesProblem {
+    pub path: String,
+    pub error: RulesError,
+}
+
+impl fmt::Display for RulesProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.error)
+    }
+}
+
+// Bad choices made by a player. The same decision can be asked for again
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputError {
+    // Nothing left to read, eg. stdin was closed or a replayed log ran out
+    EndOfInput,
+    InvalidCardIndex {
+        index: isize,
+        card_count: usize,
+    },
+    UnevenCardSwap {
+        first_count: usize,
+        second_count: usize,
+    },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::EndOfInput => write!(f, "No more input"),
+            InputError::InvalidCardIndex { index, card_count } => write!(
+                f,
+                "Invalid card index: {}. There are {} cards to choose from",
+                index, card_count,
+            ),
+            InputError::UnevenCardSwap {
+                first_count,
+                second_count,
+            } => write!(
+                f,
+                "Card swaps must move the same number of cards in both directions. Given: {} and {}",
+                first_count, second_count,
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameError {
+    Rules(RulesError),
+    Input(InputError),
+    // The engine got into a state it should never be in
+    Invariant(String),
+    // The game went on longer than it was allowed to, eg. because it stalled
+    TurnLimitReached(usize),
+    // A player was asked to decide more often than the game allowed, eg.
+    // because a turn never ends
+    DecisionLimitReached(usize),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::Rules(rules_error) => write!(f, "Game rules error: {}", rules_error),
+            GameError::Input(input_error) => write!(f, "{}", input_error),
+            GameError::Invariant(message) => write!(f, "Game engine error: {}", message),
+            GameError::TurnLimitReached(turn_limit) => {
+                write!(f, "The game was still going after {} turns", turn_limit)
+            }
+            GameError::DecisionLimitReached(decision_limit) => {
+                write!(
+                    f,
+                    "The game was still going after {} decisions",
+                    decision_limit
+                )
+            }
+        }
+    }
+}
+
+impl Error for GameError {}
+
+impl From<RulesError> for GameError {
+    fn from(rules_error: RulesError) -> Self {
+        GameError::Rules(rules_error)
+    }
+}
+
+impl From<InputError> for GameError {
+    fn from(input_error: InputError) -> Self {
+        GameError::Input(input_error)
+    }
+}
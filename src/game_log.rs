@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_error::{GameError, InputError};
+use crate::game_rules::GameRules;
+use crate::game_state::GameState;
+use crate::player::{ControllerKind, Player};
+use crate::player_controller::{PlayerController, ScriptedController};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LogEntry {
+    // Index into the actions the player was offered. Invalid picks are logged
+    // too, so a replay asks for a choice again exactly where the game did
+    ActionChosen {
+        player_index: usize,
+        action_index: usize,
+    },
+    // Indices of the cards picked while swapping or moving cards. Turned down
+    // selections are logged too
+    CardsChosen {
+        player_index: usize,
+        card_indices: Vec<usize>,
+    },
+    // Every action that ran, including consequences
+    ActionExecuted {
+        player_index: usize,
+        description: String,
+    },
+}
+
+impl LogEntry {
+    pub fn player_index(&self) -> usize {
+        match self {
+            LogEntry::ActionChosen { player_index, .. }
+            | LogEntry::CardsChosen { player_index, .. }
+            | LogEntry::ActionExecuted { player_index, .. } => *player_index,
+        }
+    }
+
+    pub fn player_choice(&self) -> bool {
+        matches!(
+            self,
+            LogEntry::ActionChosen { .. } | LogEntry::CardsChosen { .. }
+        )
+    }
+}
+
+// Everything needed to play a game again exactly as it happened. The seed
+// gives the same deal and the choices give the same play
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameLog {
+    pub seed: u64,
+    pub player_names: Vec<String>,
+    // Who played each seat, which is part of the game state. Older logs only
+    // had people playing
+    #[serde(default)]
+    pub player_controllers: Vec<ControllerKind>,
+    pub rules_fingerprint: String,
+    #[serde(default)]
+    pub undo_allowed: bool,
+    pub entries: Vec<LogEntry>,
+
+    // The game state once the game stopped, either because it was over or at
+    // the choice that was never made. None if the game was killed before it
+    // could stop
+    #[serde(default)]
+    pub state_fingerprint: Option<String>,
+}
+
+// One line of a log file. Log files are written a line at a time as the game
+// goes, so a game that's killed part way through still has its log up to
+// the decision it was killed at
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LogLine {
+    // Always the first line
+    Start {
+        seed: u64,
+        player_names: Vec<String>,
+        player_controllers: Vec<ControllerKind>,
+        rules_fingerprint: String,
+        undo_allowed: bool,
+    },
+    Entry(LogEntry),
+    // The last line, unless the game was killed
+    End {
+        state_fingerprint: String,
+    },
+}
+
+// Appends to a log file as the game goes. Each line is written straight to
+// the file rather than buffered
+#[derive(Debug)]
+pub struct LogWriter {
+    path: PathBuf,
+    file: File,
+}
+
+impl LogWriter {
+    // Starts a log file with everything logged so far
+    pub fn create(path: &Path, game_log: &GameLog) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|error| format!("Couldn't write {}: {}", path.display(), error))?;
+        let log_writer = LogWriter {
+            path: path.to_path_buf(),
+            file,
+        };
+
+        log_writer.write_line(&LogLine::Start {
+            seed: game_log.seed,
+            player_names: game_log.player_names.clone(),
+            player_controllers: game_log.player_controllers.clone(),
+            rules_fingerprint: game_log.rules_fingerprint.clone(),
+            undo_allowed: game_log.undo_allowed,
+        })?;
+        for log_entry in game_log.entries.iter() {
+            log_writer.write_entry(log_entry)?;
+        }
+
+        return Ok(log_writer);
+    }
+
+    pub fn write_entry(&self, log_entry: &LogEntry) -> Result<(), String> {
+        self.write_line(&LogLine::Entry(log_entry.clone()))
+    }
+
+    pub fn write_end(&self, state_fingerprint: &str) -> Result<(), String> {
+        self.write_line(&LogLine::End {
+            state_fingerprint: state_fingerprint.to_string(),
+        })
+    }
+
+    fn write_line(&self, log_line: &LogLine) -> Result<(), String> {
+        let mut line = serde_json::to_string(log_line)
+            .map_err(|error| format!("Couldn't serialize {}: {}", self.path.display(), error))?;
+        line.push('\n');
+
+        return (&self.file)
+            .write_all(line.as_bytes())
+            .map_err(|error| format!("Couldn't write {}: {}", self.path.display(), error));
+    }
+}
+
+impl GameLog {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let serialized = fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+        let line_problem = |line_index: usize, problem: &dyn fmt::Display| {
+            format!(
+                "Couldn't parse {} line {}: {}",
+                path.display(),
+                line_index + 1,
+                problem
+            )
+        };
+
+        let mut game_log: Option<GameLog> = None;
+        for (line_index, line) in serialized.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let log_line = serde_json::from_str::<LogLine>(line)
+                .map_err(|error| line_problem(line_index, &error))?;
+            match (game_log.as_mut(), log_line) {
+                (
+                    None,
+                    LogLine::Start {
+                        seed,
+                        player_names,
+                        player_controllers,
+                        rules_fingerprint,
+                        undo_allowed,
+                    },
+                ) => {
+                    game_log = Some(GameLog {
+                        seed,
+                        player_names,
+                        player_controllers,
+                        rules_fingerprint,
+                        undo_allowed,
+                        ..Default::default()
+                    })
+                }
+                (None, _) => return Err(line_problem(line_index, &"A log starts with its start")),
+                (Some(game_log), LogLine::Entry(log_entry)) => game_log.entries.push(log_entry),
+                (Some(game_log), LogLine::End { state_fingerprint }) => {
+                    game_log.state_fingerprint = Some(state_fingerprint)
+                }
+                (Some(_), LogLine::Start { .. }) => {
+                    return Err(line_problem(line_index, &"A log only starts once"))
+                }
+            }
+        }
+
+        return game_log
+            .ok_or_else(|| format!("Couldn't parse {}: The log is empty", path.display()));
+    }
+
+    // The choices one player made, in order
+    pub fn player_choices(&self, player_index: usize) -> impl Iterator<Item = &LogEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.player_choice() && entry.player_index() == player_index)
+    }
+
+    // Plays the game again from the start with the logged seed and choices.
+    // An unfinished game is replayed up to the choice it was waiting on
+    pub fn replay(&self, game_rules: GameRules) -> Result<GameState, GameError> {
+        let players = self
+            .player_names
+            .iter()
+            .enumerate()
+            .map(|(player_index, player_name)| Player {
+                name: player_name.clone(),
+                hand: BTreeMap::new(),
+                controller: self
+                    .player_controllers
+                    .get(player_index)
+                    .copied()
+                    .unwrap_or_default(),
+            })
+            .collect();
+        let mut game_state = GameState::new(game_rules, players, self.seed);
+        game_state.deal();
+        game_state.undo_allowed = self.undo_allowed;
+
+        let mut controllers = (0..game_state.players.len())
+            .map(|player_index| {
+                Box::new(ScriptedController::new(
+                    self.player_choices(player_index).cloned(),
+                )) as Box<dyn PlayerController>
+            })
+            .collect::<Vec<_>>();
+
+        match game_state.play_game(&mut controllers) {
+            Ok(_) | Err(GameError::Input(InputError::EndOfInput)) => Ok(game_state),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+    use crate::game_rules::poo_head_rules;
+    use crate::player_controller::bot_for;
+
+    fn temp_log_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("card-game-{}-{}.jsonl", name, process::id()))
+    }
+
+    // A seeded game between random bots, logged to the given path
+    fn play_logged_game(log_path: &Path, seed: u64) -> GameState {
+        let players = ["Ann:random", "Bob:random", "Cat:random"]
+            .iter()
+            .map(|player_spec| Player::from_spec(player_spec).unwrap())
+            .collect::<Vec<Player>>();
+        let mut controllers = players
+            .iter()
+            .enumerate()
+            .map(|(player_index, player)| bot_for(player.controller, player_index, seed).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut game_state = GameState::new(poo_head_rules(), players, seed);
+        game_state.deal();
+        game_state.log_path = Some(log_path.to_path_buf());
+        game_state.play_game(&mut controllers).unwrap();
+
+        return game_state;
+    }
+
+    #[test]
+    fn replay_ends_in_the_same_state() {
+        let log_path = temp_log_path("replay");
+        let game_state = play_logged_game(&log_path, 7);
+
+        let game_log = GameLog::load(&log_path).unwrap();
+        fs::remove_file(&log_path).unwrap();
+        assert_eq!(game_log, game_state.game_log);
+        assert_eq!(game_log.state_fingerprint, Some(game_state.fingerprint()));
+
+        let replayed_state = game_log.replay(poo_head_rules()).unwrap();
+        assert_eq!(replayed_state.fingerprint(), game_state.fingerprint());
+        assert_eq!(replayed_state.game_log.entries, game_log.entries);
+    }
+
+    #[test]
+    fn log_of_a_killed_game_replays_as_far_as_it_goes() {
+        let log_path = temp_log_path("killed");
+        let game_state = play_logged_game(&log_path, 11);
+
+        // Cut the log off part way through, like a game killed mid turn
+        let serialized = fs::read_to_string(&log_path).unwrap();
+        let kept_lines = serialized.lines().count() / 2;
+        let truncated = serialized
+            .lines()
+            .take(kept_lines)
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        fs::write(&log_path, truncated).unwrap();
+
+        let game_log = GameLog::load(&log_path).unwrap();
+        fs::remove_file(&log_path).unwrap();
+        assert_eq!(game_log.state_fingerprint, None);
+        assert_eq!(
+            game_log.entries[..],
+            game_state.game_log.entries[..kept_lines - 1]
+        );
+
+        let replayed_state = game_log.replay(poo_head_rules()).unwrap();
+        assert!(replayed_state
+            .game_log
+            .entries
+            .starts_with(&game_log.entries));
+        assert_ne!(replayed_state.fingerprint(), game_state.fingerprint());
+    }
+
+    #[test]
+    fn log_that_doesnt_start_right_reports_its_first_line() {
+        let log_path = temp_log_path("malformed");
+        fs::write(&log_path, "seed: 7\nentries: []\n").unwrap();
+
+        let error = GameLog::load(&log_path).unwrap_err();
+        fs::remove_file(&log_path).unwrap();
+        assert!(error.starts_with(&format!(
+            "Couldn't parse {} line 1: expected value",
+            log_path.display()
+        )));
+    }
+}
@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
-use text_io::read;
 
 use serde::{Deserialize, Serialize};
 
-use crate::card_deck::{Card, CardGroup, CardRank, CardValue};
+use crate::active_effects::EffectDuration;
+use crate::card_deck::{Card, CardGroup, CardRank, CardValue, DeckRanks, DeckSpecification};
+use crate::game_error::{GameError, RulesError, RulesProblem};
 use crate::game_state::GameState;
+use crate::player_controller::{CardChoice, PlayerController};
+use crate::snapshot;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum CardGroupOwner {
@@ -45,22 +49,23 @@ impl fmt::Display for CardGroupId {
 
 const COMMUNAL_CARDS: &str = "communal_cards";
 
+fn card_group_names(card_groups: &BTreeMap<String, CardGroup>) -> Vec<String> {
+    card_groups.keys().map(|k| k.to_string()).collect()
+}
+
 // TODO: DRY _mut and not mut method definitions
 
 impl CardGroupId {
     fn owners_card_groups<'a>(
         &self,
         game_state: &'a GameState,
-    ) -> Result<&'a HashMap<String, CardGroup>, String> {
+    ) -> Result<&'a BTreeMap<String, CardGroup>, GameError> {
         match &self.owner {
             CardGroupOwner::Name(owner_name) => {
                 if owner_name == COMMUNAL_CARDS {
                     Ok(&game_state.communal_cards)
                 } else {
-                    return Err(format!(
-                        "Invalid card group owner name. Right now only 'communal_cards' is supported. Given: {}",
-                        owner_name,
-                    ).into());
+                    return Err(RulesError::UnknownCardGroupOwner(owner_name.clone()).into());
                 }
             }
 
@@ -73,10 +78,10 @@ impl CardGroupId {
                 {
                     Ok(&player.hand)
                 } else {
-                    return Err(format!(
+                    return Err(GameError::Invariant(format!(
                         "Invalid player index. Given: {}. Player count: {}",
                         offset_from_current_player, player_count,
-                    ));
+                    )));
                 }
             }
         }
@@ -85,16 +90,13 @@ impl CardGroupId {
     fn owners_card_groups_mut<'a>(
         &self,
         game_state: &'a mut GameState,
-    ) -> Result<&'a mut HashMap<String, CardGroup>, String> {
+    ) -> Result<&'a mut BTreeMap<String, CardGroup>, GameError> {
         match &self.owner {
             CardGroupOwner::Name(owner_name) => {
                 if owner_name == COMMUNAL_CARDS {
                     Ok(&mut game_state.communal_cards)
                 } else {
-                    return Err(format!(
-                        "Invalid card group owner name. Right now only 'communal_cards' is supported. Given: {}",
-                        owner_name,
-                    ).into());
+                    return Err(RulesError::UnknownCardGroupOwner(owner_name.clone()).into());
                 }
             }
 
@@ -107,102 +109,162 @@ impl CardGroupId {
                 {
                     Ok(&mut player.hand)
                 } else {
-                    return Err(format!(
+                    return Err(GameError::Invariant(format!(
                         "Invalid player index. Given: {}. Player count: {}",
                         offset_from_current_player, player_count,
-                    ));
+                    )));
                 }
             }
         }
     }
 
-    // TODO: Add an error type
-    fn card_group<'a>(&self, game_state: &'a GameState) -> Result<&'a CardGroup, String> {
-        let owners_card_groups = self.owners_card_groups(game_state)?;
-        let owners_card_groups_names = owners_card_groups
-            .keys()
-            .map(|k| k.to_string())
-            .collect::<Vec<String>>()
-            .join(", ");
+    // Whether the identified group has any cards, without treating an empty
+    // 'first_with_cards_of' list as an error
+    fn has_cards(&self, game_state: &GameState) -> Result<bool, GameError> {
+        if let Some(first_with_cards_of) = &self.first_with_cards_of {
+            let owners_card_groups = self.owners_card_groups(game_state)?;
+            return Ok(first_with_cards_of.iter().any(|card_group_name| {
+                owners_card_groups
+                    .get(card_group_name)
+                    .is_some_and(|card_group| !card_group.cards.is_empty())
+            }));
+        }
 
+        return Ok(!self.card_group(game_state)?.cards.is_empty());
+    }
+
+    fn card_group<'a>(&self, game_state: &'a GameState) -> Result<&'a CardGroup, GameError> {
+        let owners_card_groups = self.owners_card_groups(game_state)?;
         if let Some(name) = &self.name {
             if owners_card_groups.contains_key(name) {
                 return Ok(owners_card_groups.get(name).unwrap());
             } else {
-                return Err(format!(
-                    "Player hand card group name doesn't match anything. Given: {}. Available: {}",
-                    name, owners_card_groups_names,
-                )
+                return Err(RulesError::UnknownCardGroup {
+                    name: name.clone(),
+                    available: card_group_names(owners_card_groups),
+                }
                 .into());
             }
         } else if let Some(first_with_cards_of) = &self.first_with_cards_of {
             for card_group_name in first_with_cards_of {
-                if owners_card_groups.contains_key(card_group_name) {
-                    if owners_card_groups.get(card_group_name).unwrap().cards.len() > 0 {
-                        return Ok(owners_card_groups.get(card_group_name).unwrap());
-                    }
+                if owners_card_groups
+                    .get(card_group_name)
+                    .is_some_and(|card_group| !card_group.cards.is_empty())
+                {
+                    return Ok(owners_card_groups.get(card_group_name).unwrap());
                 }
             }
 
-            return Err(format!(
+            return Err(GameError::Invariant(format!(
                 "None of the card groups had any cards in: {}",
-                first_with_cards_of
-                    .iter()
-                    .map(|k| k.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", "),
-            ));
+                first_with_cards_of.join(", "),
+            )));
         }
 
-        return Err(
-            "Invalid card group identifier. Neither name nor 'first_with_cards_of' set".into(),
-        );
+        return Err(RulesError::IncompleteCardGroupId.into());
+    }
+
+    fn owned_by_current_player(&self, game_state: &GameState) -> bool {
+        match &self.owner {
+            CardGroupOwner::Name(_) => false,
+            CardGroupOwner::RelativePlayer {
+                offset_from_current_player,
+            } => {
+                game_state.player_index_at_offset(*offset_from_current_player)
+                    == game_state.player_index_at_offset(0)
+            }
+        }
+    }
+
+    // The cards as the current player sees them, with None for hidden cards
+    fn visible_cards(&self, game_state: &GameState) -> Result<Vec<Option<Card>>, GameError> {
+        let seen_by_owner = self.owned_by_current_player(game_state);
+        return Ok(self
+            .card_group(game_state)?
+            .visible_cards(seen_by_owner)
+            .into_iter()
+            .map(|visible_card| visible_card.cloned())
+            .collect());
     }
 
-    // TODO: Add an error type
     fn card_group_mut<'a>(
         &self,
         game_state: &'a mut GameState,
-    ) -> Result<&'a mut CardGroup, String> {
+    ) -> Result<&'a mut CardGroup, GameError> {
         let owners_card_groups = self.owners_card_groups_mut(game_state)?;
-        let owners_card_groups_names = owners_card_groups
-            .keys()
-            .map(|k| k.to_string())
-            .collect::<Vec<String>>()
-            .join(", ");
-
         if let Some(name) = &self.name {
             if owners_card_groups.contains_key(name) {
                 return Ok(owners_card_groups.get_mut(name).unwrap());
             } else {
-                return Err(format!(
-                    "Player hand card group name doesn't match anything. Given: {}. Available: {}",
-                    name, owners_card_groups_names,
-                )
+                return Err(RulesError::UnknownCardGroup {
+                    name: name.clone(),
+                    available: card_group_names(owners_card_groups),
+                }
                 .into());
             }
         } else if let Some(first_with_cards_of) = &self.first_with_cards_of {
             for card_group_name in first_with_cards_of {
-                if owners_card_groups.contains_key(card_group_name) {
-                    if owners_card_groups.get(card_group_name).unwrap().cards.len() > 0 {
-                        return Ok(owners_card_groups.get_mut(card_group_name).unwrap());
-                    }
+                if owners_card_groups
+                    .get(card_group_name)
+                    .is_some_and(|card_group| !card_group.cards.is_empty())
+                {
+                    return Ok(owners_card_groups.get_mut(card_group_name).unwrap());
                 }
             }
 
-            return Err(format!(
+            return Err(GameError::Invariant(format!(
                 "None of the card groups had any cards in: {}",
-                first_with_cards_of
-                    .iter()
-                    .map(|k| k.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", "),
-            ));
+                first_with_cards_of.join(", "),
+            )));
         }
 
-        return Err(
-            "Invalid card group identifier. Neither name nor 'first_with_cards_of' set".into(),
-        );
+        return Err(RulesError::IncompleteCardGroupId.into());
+    }
+}
+
+// Validation of the game rules happens before any game state exists, so these
+// check identifiers against the card groups the rules define
+impl CardGroupId {
+    fn validate(&self, path: &str, game_rules: &GameRules, problems: &mut Vec<RulesProblem>) {
+        let owners_card_groups = match &self.owner {
+            CardGroupOwner::Name(owner_name) => {
+                if owner_name == COMMUNAL_CARDS {
+                    &game_rules.communal_cards
+                } else {
+                    problems.push(RulesProblem {
+                        path: format!("{}.owner.Name", path),
+                        error: RulesError::UnknownCardGroupOwner(owner_name.clone()),
+                    });
+                    return;
+                }
+            }
+            CardGroupOwner::RelativePlayer { .. } => &game_rules.player_hand,
+        };
+
+        let mut check_card_group_name = |name_path: String, name: &String| {
+            if !owners_card_groups.contains_key(name) {
+                problems.push(RulesProblem {
+                    path: name_path,
+                    error: RulesError::UnknownCardGroup {
+                        name: name.clone(),
+                        available: card_group_names(owners_card_groups),
+                    },
+                });
+            }
+        };
+
+        if let Some(name) = &self.name {
+            check_card_group_name(format!("{}.name", path), name);
+        } else if let Some(first_with_cards_of) = &self.first_with_cards_of {
+            for (i, name) in first_with_cards_of.iter().enumerate() {
+                check_card_group_name(format!("{}.first_with_cards_of[{}]", path, i), name);
+            }
+        } else {
+            problems.push(RulesProblem {
+                path: path.to_string(),
+                error: RulesError::IncompleteCardGroupId,
+            });
+        }
     }
 }
 
@@ -211,28 +273,283 @@ pub enum RelativeCard {
     LastPlayedCard,
 }
 
+impl RelativeCard {
+    fn card<'a>(&self, game_state: &'a GameState) -> Option<&'a Card> {
+        match self {
+            RelativeCard::LastPlayedCard => game_state.last_played_card.as_ref(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PlayableCardConstraint {
     MinimumPlayableCard(RelativeCard),
+    MaximumPlayableCard(RelativeCard),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayableCardConstraints {
+    constraint: PlayableCardConstraint,
+
+    #[serde(default)]
+    duration: EffectDuration,
+}
+
+// A playable card constraint with its relative card looked up at the time it
+// was applied. Wild cards can always be played past these limits
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PlayableCardLimit {
+    AtLeast(Card),
+    AtMost(Card),
+}
+
+impl fmt::Display for PlayableCardLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayableCardLimit::AtLeast(card) => {
+                write!(f, "cards must be wild or at least {}", card)
+            }
+            PlayableCardLimit::AtMost(card) => write!(f, "cards must be wild or at most {}", card),
+        }
+    }
+}
+
+impl PlayableCardLimit {
+    fn met(&self, cards: &[Card], game_state: &GameState) -> Result<bool, GameError> {
+        let (limit_card, card_must_be_at_least_limit) = match self {
+            PlayableCardLimit::AtLeast(card) => (card, true),
+            PlayableCardLimit::AtMost(card) => (card, false),
+        };
+
+        let limit_value = match game_state.card_value(&limit_card.rank)? {
+            CardValue::Numeric(limit_value) => limit_value,
+            CardValue::Wild => return Ok(true),
+        };
+
+        for card in cards.iter() {
+            if let CardValue::Numeric(value) = game_state.card_value(&card.rank)? {
+                if (card_must_be_at_least_limit && value < limit_value)
+                    || (!card_must_be_at_least_limit && value > limit_value)
+                {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
 }
 
 // Maybe all the verbs should implement a trait?
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum CardMoveCount {
+    // Moves every card in the source group, keeping their order
+    #[default]
+    All,
+    // The acting player picks which cards to move
+    PlayerChooses,
+    // Moves cards off the top of the source until the destination has this many
+    UntilDestinationHas(usize),
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct CardMove {
     card_group_name_source: CardGroupId,
     card_group_name_dest: CardGroupId,
 
+    #[serde(default)]
+    count: CardMoveCount,
+
     #[serde(default)]
     card_conditions: Vec<CardCondition>,
 }
 
 impl CardMove {
-    fn execute(&self, game_state: &mut GameState) -> Result<(), String> {
-        // TODO: Prompt user to figure out what cards they want to move
-        // See if their selection meets the card conditions
-        // See if their selection is OK by the active consequences
-        // If so, move them
+    // A move is only available if it can actually move something. For player
+    // chosen moves that means at least one card satisfies the card conditions,
+    // which is enough because every condition that holds for a selection also
+    // holds for each card in it. A card the player can't see could be anything,
+    // so it always counts, otherwise availability would give it away
+    fn available(&self, game_state: &GameState) -> Result<bool, GameError> {
+        if !self.card_group_name_source.has_cards(game_state)? {
+            return Ok(false);
+        }
+
+        match self.count {
+            CardMoveCount::All => Ok(true),
+            CardMoveCount::PlayerChooses => {
+                for visible_card in self.card_group_name_source.visible_cards(game_state)? {
+                    match visible_card {
+                        None => return Ok(true),
+                        Some(card) => {
+                            if self
+                                .selection_problem(std::slice::from_ref(&card), game_state)?
+                                .is_none()
+                            {
+                                return Ok(true);
+                            }
+                        }
+                    }
+                }
+
+                Ok(false)
+            }
+            CardMoveCount::UntilDestinationHas(card_count) => Ok(self
+                .card_group_name_dest
+                .card_group(game_state)?
+                .cards
+                .len()
+                < card_count),
+        }
+    }
+
+    // Describes why a selection of cards can't be played, either because of
+    // this move's card conditions or the playable card limits in effect
+    pub fn selection_problem(
+        &self,
+        cards: &[Card],
+        game_state: &GameState,
+    ) -> Result<Option<String>, GameError> {
+        let destination = self.card_group_name_dest.card_group(game_state)?;
+        for card_condition in self.card_conditions.iter() {
+            if !card_condition.met(cards, destination, game_state)? {
+                return Ok(Some(card_condition.to_string()));
+            }
+        }
+
+        for playable_card_limit in game_state.playable_card_limits() {
+            if !playable_card_limit.met(cards, game_state)? {
+                return Ok(Some(playable_card_limit.to_string()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // How many cards the move adds to the current player's cards, negative
+    // when it takes them away. Moves the player picks cards for count as one
+    // card, the fewest they can move
+    fn card_count_change(&self, game_state: &GameState) -> Result<isize, GameError> {
+        if !self.card_group_name_source.has_cards(game_state)? {
+            return Ok(0);
+        }
+
+        let source_card_count = self
+            .card_group_name_source
+            .card_group(game_state)?
+            .cards
+            .len();
+        let move_count = match self.count {
+            CardMoveCount::All => source_card_count,
+            CardMoveCount::PlayerChooses => 1,
+            CardMoveCount::UntilDestinationHas(card_count) => card_count
+                .saturating_sub(
+                    self.card_group_name_dest
+                        .card_group(game_state)?
+                        .cards
+                        .len(),
+                )
+                .min(source_card_count),
+        };
+
+        let gained = self
+            .card_group_name_dest
+            .owned_by_current_player(game_state) as isize
+            - self
+                .card_group_name_source
+                .owned_by_current_player(game_state) as isize;
+        return Ok(gained * move_count as isize);
+    }
+
+    fn execute(
+        &self,
+        game_state: &mut GameState,
+        controller: &mut dyn PlayerController,
+    ) -> Result<(), GameError> {
+        let cards_to_move = match self.count {
+            CardMoveCount::All => self
+                .card_group_name_source
+                .card_group_mut(game_state)?
+                .cards
+                .drain(..)
+                .collect::<Vec<Card>>(),
+            CardMoveCount::PlayerChooses => {
+                let card_choice = CardChoice::new(
+                    self.card_group_name_source.to_string(),
+                    self.card_group_name_dest.to_string(),
+                    self.card_group_name_source.visible_cards(game_state)?,
+                    self.card_group_name_dest.visible_cards(game_state)?,
+                    Some(self),
+                );
+                let selected_card_indices = game_state.choose_cards(controller, &card_choice)?;
+                let cards_to_move = {
+                    let source_cards = &self.card_group_name_source.card_group(game_state)?.cards;
+                    selected_card_indices
+                        .iter()
+                        .map(|i| source_cards[*i].clone())
+                        .collect::<Vec<Card>>()
+                };
+
+                // The player could only be held to the card conditions for the
+                // cards they could see. Any they couldn't are turned over once
+                // they're committed to, and if the whole selection turns out
+                // not to meet the conditions the move fails. The cards are
+                // still moved, and the rules decide what failing costs
+                let picked_hidden_cards = selected_card_indices
+                    .iter()
+                    .any(|i| card_choice.visible_source_cards[*i].is_none());
+                let failed = picked_hidden_cards
+                    && self
+                        .selection_problem(&cards_to_move, game_state)?
+                        .is_some();
+
+                let source_cards = &mut self
+                    .card_group_name_source
+                    .card_group_mut(game_state)?
+                    .cards;
+
+                // Remove from the back so the remaining indices stay valid
+                let mut removal_order = selected_card_indices;
+                removal_order.sort_unstable();
+                for i in removal_order.into_iter().rev() {
+                    source_cards.remove(i);
+                }
+
+                // Controllers aren't told about a failed move. Messages are
+                // for choices that were turned down, and this one wasn't
+                game_state.last_card_move_failed = failed;
+                if !failed {
+                    game_state.last_played_card = cards_to_move.last().cloned();
+                }
+
+                cards_to_move
+            }
+            CardMoveCount::UntilDestinationHas(card_count) => {
+                let destination_card_count = self
+                    .card_group_name_dest
+                    .card_group(game_state)?
+                    .cards
+                    .len();
+                let source_cards = &mut self
+                    .card_group_name_source
+                    .card_group_mut(game_state)?
+                    .cards;
+                let move_count = card_count
+                    .saturating_sub(destination_card_count)
+                    .min(source_cards.len());
+
+                // The top of a card group is the end of its card list
+                let mut cards_to_move = source_cards.split_off(source_cards.len() - move_count);
+                cards_to_move.reverse();
+                cards_to_move
+            }
+        };
+
+        self.card_group_name_dest
+            .card_group_mut(game_state)?
+            .add_cards(cards_to_move);
+
         Ok(())
     }
 }
@@ -245,10 +562,12 @@ pub struct CardSwap {
 
 // Swaps cards between two card groups
 impl CardSwap {
-    fn execute(&self, game_state: &mut GameState) -> Result<(), String> {
-        // TODO: User input errors should trigger input retries
-
-        let card_groups = vec![&self.first_card_group, &self.second_card_group];
+    fn execute(
+        &self,
+        game_state: &mut GameState,
+        controller: &mut dyn PlayerController,
+    ) -> Result<(), GameError> {
+        let card_groups = [&self.first_card_group, &self.second_card_group];
 
         // This function reflects the length of card_groups above
         let other_card_group_id =
@@ -257,42 +576,27 @@ impl CardSwap {
         let mut cards_to_move_by_card_group: Vec<Vec<Card>> = vec![vec![], vec![]];
 
         for (card_group_index, card_group_id) in card_groups.iter().enumerate() {
-            loop {
-                println!(
-                    "Select card to move from {} into {}. Use 0-{} or -1 to finish:",
-                    card_group_id,
-                    other_card_group_id(card_group_index),
-                    &card_group_id.card_group(game_state)?.cards.len() - 1,
-                );
-
-                let selected_card_index: isize = read!();
-                if selected_card_index < 0 {
-                    break;
-                }
-
-                if selected_card_index as usize >= card_group_id.card_group(game_state)?.cards.len()
-                {
-                    return Err(format!("Invalid card index: {}", selected_card_index));
-                }
-
-                cards_to_move_by_card_group[card_group_index].push(
-                    card_group_id
-                        .card_group(game_state)?
-                        .cards
-                        .get(selected_card_index as usize)
-                        .unwrap()
-                        .clone(),
-                );
+            let card_group_cards = card_group_id.card_group(game_state)?.cards.clone();
+            let mut card_choice = CardChoice::new(
+                card_group_id.to_string(),
+                other_card_group_id(card_group_index).to_string(),
+                card_group_id.visible_cards(game_state)?,
+                other_card_group_id(card_group_index).visible_cards(game_state)?,
+                None,
+            );
+            if card_group_index > 0 {
+                card_choice.required_count = Some(cards_to_move_by_card_group[0].len());
             }
-        }
 
-        if cards_to_move_by_card_group[0].len() != cards_to_move_by_card_group[1].len() {
-            return Err("Card swaps must move the same number of cards in both directions".into());
+            for card_index in game_state.choose_cards(controller, &card_choice)? {
+                cards_to_move_by_card_group[card_group_index]
+                    .push(card_group_cards[card_index].clone());
+            }
         }
 
         for (card_group_index, (card_group_id, cards_to_move_out_of_card_group)) in card_groups
             .iter()
-            .zip(cards_to_move_by_card_group.into_iter())
+            .zip(cards_to_move_by_card_group)
             .enumerate()
         {
             let card_group_cards = &mut card_group_id.card_group_mut(game_state)?.cards;
@@ -303,8 +607,7 @@ impl CardSwap {
             for card in cards_to_move_out_of_card_group.into_iter() {
                 other_card_group_id(card_group_index)
                     .card_group_mut(game_state)?
-                    .cards
-                    .push(card);
+                    .add_cards([card]);
             }
         }
 
@@ -320,11 +623,14 @@ pub struct TurnMove {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ActionDescriptions {
     action_descriptions: Vec<String>,
+
+    #[serde(default)]
+    duration: EffectDuration,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Verb {
-    ConstrainPlayableCards(PlayableCardConstraint),
+    ConstrainPlayableCards(PlayableCardConstraints),
     EndPhase,
     ExcludeActions(ActionDescriptions),
     MoveCards(CardMove),
@@ -332,6 +638,63 @@ pub enum Verb {
     SwapCards(CardSwap),
 }
 
+impl Verb {
+    fn validate(
+        &self,
+        path: &str,
+        game_rules: &GameRules,
+        action_descriptions: &HashSet<&str>,
+        problems: &mut Vec<RulesProblem>,
+    ) {
+        match self {
+            Verb::MoveCards(card_move) => {
+                card_move.card_group_name_source.validate(
+                    &format!("{}.MoveCards.card_group_name_source", path),
+                    game_rules,
+                    problems,
+                );
+                card_move.card_group_name_dest.validate(
+                    &format!("{}.MoveCards.card_group_name_dest", path),
+                    game_rules,
+                    problems,
+                );
+            }
+            Verb::SwapCards(card_swap) => {
+                card_swap.first_card_group.validate(
+                    &format!("{}.SwapCards.first_card_group", path),
+                    game_rules,
+                    problems,
+                );
+                card_swap.second_card_group.validate(
+                    &format!("{}.SwapCards.second_card_group", path),
+                    game_rules,
+                    problems,
+                );
+            }
+            Verb::ExcludeActions(excluded_actions) => {
+                for (i, action_description) in
+                    excluded_actions.action_descriptions.iter().enumerate()
+                {
+                    if !action_descriptions.contains(action_description.as_str()) {
+                        problems.push(RulesProblem {
+                            path: format!("{}.ExcludeActions.action_descriptions[{}]", path, i),
+                            error: RulesError::UnknownActionDescription(action_description.clone()),
+                        });
+                    }
+                }
+            }
+            Verb::ConstrainPlayableCards(_) | Verb::EndPhase | Verb::MoveNextTurn(_) => {}
+        }
+    }
+
+    fn available(&self, game_state: &GameState) -> Result<bool, GameError> {
+        match self {
+            Verb::MoveCards(card_move) => card_move.available(game_state),
+            _ => Ok(true),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum Operator {
     Equal,
@@ -356,9 +719,57 @@ impl Operator {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum CardCondition {
     CardsMustBeSameRank,
+    // Every card must be wild or valued at or above the top card of the
+    // destination group. Anything can be played onto a wild card
     CardsMustBeHigherRankThanLastPlayedOrWild,
 }
 
+impl fmt::Display for CardCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CardCondition::CardsMustBeSameRank => write!(f, "cards must all be the same rank"),
+            CardCondition::CardsMustBeHigherRankThanLastPlayedOrWild => write!(
+                f,
+                "cards must be wild or at least as high as the last played card"
+            ),
+        }
+    }
+}
+
+impl CardCondition {
+    fn met(
+        &self,
+        cards: &[Card],
+        destination: &CardGroup,
+        game_state: &GameState,
+    ) -> Result<bool, GameError> {
+        match self {
+            CardCondition::CardsMustBeSameRank => {
+                Ok(cards.windows(2).all(|pair| pair[0].rank == pair[1].rank))
+            }
+            CardCondition::CardsMustBeHigherRankThanLastPlayedOrWild => {
+                let last_played_value = match destination.cards.last() {
+                    Some(last_played_card) => game_state.card_value(&last_played_card.rank)?,
+                    None => return Ok(true),
+                };
+
+                for card in cards.iter() {
+                    match (game_state.card_value(&card.rank)?, last_played_value) {
+                        (CardValue::Numeric(value), CardValue::Numeric(last_played_value)) => {
+                            if value < last_played_value {
+                                return Ok(false);
+                            }
+                        }
+                        _ => continue,
+                    }
+                }
+
+                Ok(true)
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 enum Condition {
     LastPlayedCardRank {
@@ -380,18 +791,61 @@ enum Condition {
         compare_to: usize,
         for_players: PlayerCount,
     },
+    // Compares the total number of cards held by the current player
+    PlayerCardCount {
+        operator: Operator,
+        compare_to: usize,
+    },
+    // Met when the cards picked in the last player chosen card move were
+    // turned over and didn't meet its card conditions, eg. an unplayable
+    // blind card
+    LastCardMoveFailed,
 }
 
 impl Condition {
-    fn met(&self, game_state: &mut GameState) -> Result<bool, String> {
+    fn validate(&self, path: &str, game_rules: &GameRules, problems: &mut Vec<RulesProblem>) {
+        match self {
+            Condition::LastPlayedCardRank {
+                card_group_name,
+                equals,
+            } => {
+                card_group_name.validate(
+                    &format!("{}.LastPlayedCardRank.card_group_name", path),
+                    game_rules,
+                    problems,
+                );
+                if CardRank::try_from_usize(*equals).is_none() {
+                    problems.push(RulesProblem {
+                        path: format!("{}.LastPlayedCardRank.equals", path),
+                        error: RulesError::UnknownRank(*equals),
+                    });
+                }
+            }
+            Condition::CardGroupSize {
+                card_group_name, ..
+            } => card_group_name.validate(
+                &format!("{}.CardGroupSize.card_group_name", path),
+                game_rules,
+                problems,
+            ),
+            Condition::TurnCount { .. }
+            | Condition::CardCount { .. }
+            | Condition::PlayerCardCount { .. }
+            | Condition::LastCardMoveFailed => {}
+        }
+    }
+
+    fn met(&self, game_state: &GameState) -> Result<bool, GameError> {
         match self {
             Condition::LastPlayedCardRank {
                 card_group_name,
                 equals,
             } => {
                 let card_group = card_group_name.card_group(game_state)?;
+                let rank =
+                    CardRank::try_from_usize(*equals).ok_or(RulesError::UnknownRank(*equals))?;
                 if let Some(last_card_in_group) = card_group.cards.last() {
-                    Ok(last_card_in_group.rank == CardRank::from_usize(*equals))
+                    Ok(last_card_in_group.rank == rank)
                 } else {
                     Ok(false)
                 }
@@ -420,14 +874,7 @@ impl Condition {
                         .players
                         .iter()
                         .fold(0, |players_with_card_count, player| {
-                            if operator.compare(
-                                player
-                                    .hand
-                                    .values()
-                                    .map(|hand_card_group| hand_card_group.cards.len())
-                                    .sum(),
-                                *compare_to,
-                            ) {
+                            if operator.compare(player.card_count(), *compare_to) {
                                 players_with_card_count + 1
                             } else {
                                 players_with_card_count
@@ -446,10 +893,36 @@ impl Condition {
                     }
                 }
             }
+
+            Condition::PlayerCardCount {
+                operator,
+                compare_to,
+            } => {
+                let player_count = game_state.players.len();
+                if let Some(player) = game_state.offset_from_current_player(0) {
+                    Ok(operator.compare(player.card_count(), *compare_to))
+                } else {
+                    Err(GameError::Invariant(format!(
+                        "Invalid current player. Player count: {}",
+                        player_count
+                    )))
+                }
+            }
+
+            Condition::LastCardMoveFailed => Ok(game_state.last_card_move_failed),
         }
     }
 }
 
+// Tracks the state changes that have already been claimed while resolving an
+// action and its consequences. Only the first consequence to claim one gets to
+// apply it, so the highest priority consequence wins a conflict
+#[derive(Default)]
+struct Resolution {
+    phase_ended: bool,
+    next_turn_moved: bool,
+}
+
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Action {
     pub description: String,
@@ -460,6 +933,11 @@ pub struct Action {
 
     #[serde(default)]
     consequences: Vec<Action>,
+
+    // Triggered consequences run from highest to lowest priority. Ties run in
+    // the order they're listed
+    #[serde(default)]
+    priority: i32,
 }
 
 impl fmt::Debug for Action {
@@ -475,36 +953,151 @@ impl fmt::Display for Action {
 }
 
 impl Action {
-    fn available(&self, game_state: &mut GameState) -> Result<bool, String> {
-        for condition in self.conditions.iter() {
-            if !condition.met(game_state)? {
-                return Ok(false);
-            }
+    fn validate(
+        &self,
+        path: &str,
+        game_rules: &GameRules,
+        action_descriptions: &HashSet<&str>,
+        problems: &mut Vec<RulesProblem>,
+    ) {
+        self.verb.validate(
+            &format!("{}.verb", path),
+            game_rules,
+            action_descriptions,
+            problems,
+        );
+
+        for (i, condition) in self.conditions.iter().enumerate() {
+            condition.validate(&format!("{}.conditions[{}]", path, i), game_rules, problems);
+        }
+
+        for (i, consequence) in self.consequences.iter().enumerate() {
+            consequence.validate(
+                &format!("{}.consequences[{}]", path, i),
+                game_rules,
+                action_descriptions,
+                problems,
+            );
+        }
+    }
+
+    fn collect_descriptions<'a>(&'a self, action_descriptions: &mut HashSet<&'a str>) {
+        action_descriptions.insert(&self.description);
+        for consequence in self.consequences.iter() {
+            consequence.collect_descriptions(action_descriptions);
+        }
+    }
+
+    fn conditions_met(&self, game_state: &GameState) -> Result<bool, GameError> {
+        all_conditions_met(&self.conditions, game_state)
+    }
+
+    fn available(&self, game_state: &GameState) -> Result<bool, GameError> {
+        if !self.conditions_met(game_state)? {
+            return Ok(false);
         }
 
-        // TODO: Check if action is ruled out by any of the active consequences
+        if game_state.action_excluded(&self.description) {
+            return Ok(false);
+        }
 
-        return Ok(true);
+        return self.verb.available(game_state);
     }
 
-    pub fn execute(&self, game_state: &mut GameState) -> Result<(), String> {
-        println!("{:?}", &self.verb);
+    // How many cards the action adds to the current player's cards, not
+    // counting its consequences. Bots use it to weigh actions up
+    pub fn card_count_change(&self, game_state: &GameState) -> Result<isize, GameError> {
         match &self.verb {
-            Verb::MoveCards(card_move) => {
-                card_move.execute(game_state)?;
+            Verb::MoveCards(card_move) => card_move.card_count_change(game_state),
+            _ => Ok(0),
+        }
+    }
+
+    // Any choices the action needs are made by the controller of the player
+    // on turn
+    pub fn execute(
+        &self,
+        game_state: &mut GameState,
+        controller: &mut dyn PlayerController,
+    ) -> Result<(), GameError> {
+        self.resolve(game_state, controller, &mut Resolution::default())
+    }
 
-                Ok(())
+    fn resolve(
+        &self,
+        game_state: &mut GameState,
+        controller: &mut dyn PlayerController,
+        resolution: &mut Resolution,
+    ) -> Result<(), GameError> {
+        game_state.log_action_executed(&self.description);
+        match &self.verb {
+            Verb::MoveCards(card_move) => {
+                card_move.execute(game_state, controller)?;
             }
             Verb::SwapCards(card_swap) => {
-                card_swap.execute(game_state)?;
+                card_swap.execute(game_state, controller)?;
+            }
+            Verb::EndPhase => {
+                if !resolution.phase_ended {
+                    resolution.phase_ended = true;
+                    game_state.end_phase();
+                }
+            }
+            Verb::ConstrainPlayableCards(playable_card_constraints) => {
+                let playable_card_limit = match &playable_card_constraints.constraint {
+                    PlayableCardConstraint::MinimumPlayableCard(relative_card) => relative_card
+                        .card(game_state)
+                        .map(|card| PlayableCardLimit::AtLeast(card.clone())),
+                    PlayableCardConstraint::MaximumPlayableCard(relative_card) => relative_card
+                        .card(game_state)
+                        .map(|card| PlayableCardLimit::AtMost(card.clone())),
+                };
+
+                // Nothing to constrain against if no card has been played yet
+                if let Some(playable_card_limit) = playable_card_limit {
+                    game_state.limit_playable_cards(
+                        playable_card_limit,
+                        playable_card_constraints.duration.clone(),
+                    );
+                }
+            }
+            Verb::ExcludeActions(action_descriptions) => {
+                for action_description in action_descriptions.action_descriptions.iter() {
+                    game_state.exclude_action(
+                        action_description.clone(),
+                        action_descriptions.duration.clone(),
+                    );
+                }
+            }
+            Verb::MoveNextTurn(turn_move) => {
+                if !resolution.next_turn_moved {
+                    resolution.next_turn_moved = true;
+                    game_state.move_next_turn(turn_move.offset_from_current_player);
+                }
+            }
+        }
 
-                Ok(())
+        // Every consequence is checked against the state this verb left behind
+        // before any of them run, so one consequence can't change whether
+        // another one applies. Eg. discarding the active pile on a ten doesn't
+        // stop the "play again" consequence that also looks for the ten
+        let mut triggered_consequences = vec![];
+        for consequence in self.consequences.iter() {
+            if consequence.conditions_met(game_state)? {
+                triggered_consequences.push(consequence);
             }
-            _ => panic!("AHHHHHHHHHHHHHHHHHHHHHHHH"),
         }
+        triggered_consequences.sort_by_key(|consequence| Reverse(consequence.priority));
+
+        for consequence in triggered_consequences {
+            consequence.resolve(game_state, controller, resolution)?;
+        }
+
+        Ok(())
     }
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TurnRange {
     Bounded { min: usize, max: usize },
@@ -525,7 +1118,7 @@ pub enum PlayerCount {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TurnPhase {
-    name: String,
+    pub name: String,
     actions: Vec<Action>,
 }
 
@@ -540,31 +1133,641 @@ pub struct TurnType {
 pub struct GameRules {
     pub min_player_count: usize,
     pub max_player_count: usize,
-    pub player_hand: HashMap<String, CardGroup>,
-    pub communal_cards: HashMap<String, CardGroup>,
-    pub cards: HashMap<CardRank, CardDescription>,
+    pub player_hand: BTreeMap<String, CardGroup>,
+    pub communal_cards: BTreeMap<String, CardGroup>,
+    pub cards: BTreeMap<CardRank, CardDescription>,
     pub game_flow: Vec<TurnType>,
+
+    #[serde(default)]
+    pub deck: DeckSpecification,
+
+    // The game is over once all of these are met
+    #[serde(default)]
+    end_conditions: Vec<Condition>,
+
+    // Checked for each player as if it were their turn. Players who meet all
+    // of these are out of the game and skipped from then on
+    #[serde(default)]
+    player_finished_conditions: Vec<Condition>,
+}
+
+fn all_conditions_met(conditions: &[Condition], game_state: &GameState) -> Result<bool, GameError> {
+    for condition in conditions.iter() {
+        if !condition.met(game_state)? {
+            return Ok(false);
+        }
+    }
+
+    return Ok(true);
 }
 
 impl GameRules {
-    pub fn available_actions(&self, game_state: &mut GameState) -> Result<Vec<&Action>, String> {
-        self.game_flow.iter().try_fold(
-            Vec::new(),
-            |mut available_actions, turn_type| -> Result<Vec<&Action>, String> {
-                for condition in turn_type.conditions.iter() {
-                    if condition.met(game_state)? {
-                        for turn_phase in turn_type.turn_phases.iter() {
-                            for action in turn_phase.actions.iter() {
-                                if action.available(game_state)? {
-                                    available_actions.push(action);
-                                }
-                            }
-                        }
-                    }
+    // Checks the rules for mistakes that would otherwise only show up part way
+    // through a game. Every problem found is returned, not just the first
+    pub fn validate(&self) -> Vec<RulesProblem> {
+        let mut problems = vec![];
+
+        if self.min_player_count == 0 || self.min_player_count > self.max_player_count {
+            problems.push(RulesProblem {
+                path: "min_player_count".into(),
+                error: RulesError::InvalidPlayerCounts {
+                    min_player_count: self.min_player_count,
+                    max_player_count: self.max_player_count,
+                },
+            });
+        }
+
+        if self.deck.card_count() == 0 {
+            problems.push(RulesProblem {
+                path: "deck".into(),
+                error: RulesError::EmptyDeck,
+            });
+        }
+
+        if let DeckSpecification::Standard {
+            ranks: DeckRanks::Only(ranks),
+            ..
+        } = &self.deck
+        {
+            for (i, rank) in ranks.iter().enumerate() {
+                if *rank == CardRank::Joker {
+                    problems.push(RulesProblem {
+                        path: format!("deck.Standard.ranks.Only[{}]", i),
+                        error: RulesError::JokerInDeckRanks,
+                    });
                 }
+            }
+        }
+
+        if let DeckSpecification::Cards(cards) = &self.deck {
+            for (i, card) in cards.iter().enumerate() {
+                if !card.valid() {
+                    problems.push(RulesProblem {
+                        path: format!("deck.Cards[{}]", i),
+                        error: RulesError::InvalidCard(card.clone()),
+                    });
+                }
+            }
+        }
+
+        for rank in self.deck.ranks() {
+            if !self.cards.contains_key(&rank) {
+                problems.push(RulesProblem {
+                    path: "cards".into(),
+                    error: RulesError::MissingCardDescription(rank),
+                });
+            }
+        }
 
-                return Ok(available_actions);
-            },
+        for (i, condition) in self.end_conditions.iter().enumerate() {
+            condition.validate(&format!("end_conditions[{}]", i), self, &mut problems);
+        }
+
+        for (i, condition) in self.player_finished_conditions.iter().enumerate() {
+            condition.validate(
+                &format!("player_finished_conditions[{}]", i),
+                self,
+                &mut problems,
+            );
+        }
+
+        let mut action_descriptions = HashSet::new();
+        for turn_type in self.game_flow.iter() {
+            for turn_phase in turn_type.turn_phases.iter() {
+                for action in turn_phase.actions.iter() {
+                    action.collect_descriptions(&mut action_descriptions);
+                }
+            }
+        }
+
+        for (i, turn_type) in self.game_flow.iter().enumerate() {
+            for (j, condition) in turn_type.conditions.iter().enumerate() {
+                condition.validate(
+                    &format!("game_flow[{}].conditions[{}]", i, j),
+                    self,
+                    &mut problems,
+                );
+            }
+
+            for (j, turn_phase) in turn_type.turn_phases.iter().enumerate() {
+                for (k, action) in turn_phase.actions.iter().enumerate() {
+                    action.validate(
+                        &format!("game_flow[{}].turn_phases[{}].actions[{}]", i, j, k),
+                        self,
+                        &action_descriptions,
+                        &mut problems,
+                    );
+                }
+            }
+        }
+
+        return problems;
+    }
+
+    // A hash of the parsed rules. Reformatting or commenting a rules file
+    // leaves it the same, any change to the rules themselves doesn't
+    pub fn fingerprint(&self) -> String {
+        snapshot::fingerprint(self)
+    }
+
+    pub fn game_over(&self, game_state: &GameState) -> Result<bool, GameError> {
+        if self.end_conditions.is_empty() {
+            return Ok(false);
+        }
+
+        return all_conditions_met(&self.end_conditions, game_state);
+    }
+
+    pub fn current_player_finished(&self, game_state: &GameState) -> Result<bool, GameError> {
+        if self.player_finished_conditions.is_empty() {
+            return Ok(false);
+        }
+
+        return all_conditions_met(&self.player_finished_conditions, game_state);
+    }
+
+    // The turn type of a turn is fixed when the turn starts. It's the first
+    // one in the game flow whose conditions are all met
+    pub fn select_turn_type(&self, game_state: &GameState) -> Result<Option<usize>, GameError> {
+        for (turn_type_index, turn_type) in self.game_flow.iter().enumerate() {
+            if all_conditions_met(&turn_type.conditions, game_state)? {
+                return Ok(Some(turn_type_index));
+            }
+        }
+
+        return Ok(None);
+    }
+
+    pub fn turn_phases(&self, turn_type_index: Option<usize>) -> &[TurnPhase] {
+        match turn_type_index.and_then(|i| self.game_flow.get(i)) {
+            Some(turn_type) => &turn_type.turn_phases,
+            None => &[],
+        }
+    }
+
+    pub fn available_actions(&self, game_state: &GameState) -> Result<Vec<&Action>, GameError> {
+        let mut available_actions = Vec::new();
+
+        if let Some(turn_phase) = self
+            .turn_phases(game_state.turn_type_index)
+            .get(game_state.turn_phase_index)
+        {
+            for action in turn_phase.actions.iter() {
+                if action.available(game_state)? {
+                    available_actions.push(action);
+                }
+            }
+        }
+
+        return Ok(available_actions);
+    }
+}
+
+// The bundled rules, which most tests play by
+#[cfg(test)]
+pub const POO_HEAD_RULES_YAML: &str = include_str!("../poo_head_rules.yaml");
+
+#[cfg(test)]
+pub fn poo_head_rules() -> GameRules {
+    serde_yaml::from_str(POO_HEAD_RULES_YAML).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_deck::Suit;
+    use crate::game_error::RulesProblem;
+    use crate::game_log::LogEntry;
+    use crate::player::Player;
+    use crate::player_controller::ScriptedController;
+
+    const PLAY_CARDS: &str = "Play cards";
+    const PICK_UP: &str = "Pick-up the active pile into your hand";
+    const DRAW: &str = "Draw from the deck until you have three cards in hand";
+
+    fn card(rank: usize) -> Card {
+        Card::new(Suit::Club, CardRank::from_usize(rank))
+    }
+
+    fn cards(ranks: &[usize]) -> Vec<Card> {
+        ranks.iter().map(|rank| card(*rank)).collect()
+    }
+
+    // A two player game past setup with nothing dealt, so each test can lay
+    // out just the cards it needs
+    fn game_turn() -> GameState {
+        let players = vec![
+            Player::from_spec("Ann").unwrap(),
+            Player::from_spec("Bob").unwrap(),
+        ];
+        let mut game_state = GameState::new(poo_head_rules(), players, 0);
+        game_state.turn_count = 1;
+        game_state.start_turn().unwrap();
+
+        return game_state;
+    }
+
+    fn hand<'a>(
+        game_state: &'a mut GameState,
+        player_index: usize,
+        name: &str,
+    ) -> &'a mut Vec<Card> {
+        &mut game_state.players[player_index]
+            .hand
+            .get_mut(name)
+            .unwrap()
+            .cards
+    }
+
+    fn communal<'a>(game_state: &'a mut GameState, name: &str) -> &'a mut Vec<Card> {
+        &mut game_state.communal_cards.get_mut(name).unwrap().cards
+    }
+
+    fn available_action(game_state: &GameState, description: &str) -> Option<Action> {
+        game_state
+            .game_rules()
+            .available_actions(game_state)
+            .unwrap()
+            .into_iter()
+            .find(|action| action.description == description)
+            .cloned()
+    }
+
+    // Takes an action for the current player with the given card selections.
+    // Asking for more selections than given fails with EndOfInput
+    fn take_action(
+        game_state: &mut GameState,
+        description: &str,
+        selections: Vec<Vec<usize>>,
+    ) -> Result<(), GameError> {
+        let action = available_action(game_state, description)
+            .unwrap_or_else(|| panic!("{} isn't available", description));
+        let mut controller = ScriptedController::new(selections.into_iter().map(|card_indices| {
+            LogEntry::CardsChosen {
+                player_index: 0,
+                card_indices,
+            }
+        }));
+
+        return action.execute(game_state, &mut controller);
+    }
+
+    // The bundled rules with the first occurrence of each text replaced
+    fn rules_with(replacements: &[(&str, &str)]) -> GameRules {
+        let mut rules_yaml = POO_HEAD_RULES_YAML.to_string();
+        for (from, to) in replacements {
+            assert!(rules_yaml.contains(from), "No {} in the rules", from);
+            rules_yaml = rules_yaml.replacen(from, to, 1);
+        }
+
+        return serde_yaml::from_str(&rules_yaml).unwrap();
+    }
+
+    #[test]
+    fn bundled_rules_are_valid() {
+        assert_eq!(poo_head_rules().validate(), vec![]);
+    }
+
+    #[test]
+    fn cards_without_a_description_are_reported() {
+        let mut game_rules = poo_head_rules();
+        game_rules.cards.remove(&CardRank::Ace);
+
+        assert_eq!(
+            game_rules.validate(),
+            vec![RulesProblem {
+                path: "cards".into(),
+                error: RulesError::MissingCardDescription(CardRank::Ace),
+            }]
+        );
+    }
+
+    #[test]
+    fn ranks_that_dont_exist_are_reported() {
+        let game_rules = rules_with(&[("equals: 3", "equals: 15")]);
+
+        assert_eq!(
+            game_rules.validate(),
+            vec![RulesProblem {
+                path: "game_flow[1].turn_phases[0].actions[1].consequences[1].conditions[0].LastPlayedCardRank.equals".into(),
+                error: RulesError::UnknownRank(15),
+            }]
+        );
+    }
+
+    #[test]
+    fn misspelled_card_group_names_are_reported_with_the_names_there_are() {
+        let game_rules = rules_with(&[("name: active_pile", "name: active_pille")]);
+
+        assert_eq!(
+            game_rules.validate(),
+            vec![RulesProblem {
+                path: "game_flow[1].turn_phases[0].actions[0].verb.MoveCards.card_group_name_source.name".into(),
+                error: RulesError::UnknownCardGroup {
+                    name: "active_pille".into(),
+                    available: vec![
+                        "active_pile".into(),
+                        "discard_pile".into(),
+                        "draw_pile".into(),
+                    ],
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn jokers_in_a_standard_decks_ranks_are_reported() {
+        let game_rules = rules_with(&[(
+            "  Standard:\n    count: 1\n",
+            "  Standard:\n    count: 1\n    ranks:\n      Only: [Ace, Joker]\n",
+        )]);
+
+        assert_eq!(
+            game_rules.validate(),
+            vec![
+                RulesProblem {
+                    path: "deck.Standard.ranks.Only[1]".into(),
+                    error: RulesError::JokerInDeckRanks,
+                },
+                RulesProblem {
+                    path: "cards".into(),
+                    error: RulesError::MissingCardDescription(CardRank::Joker),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn minimum_player_count_over_the_maximum_is_reported() {
+        let mut game_rules = poo_head_rules();
+        game_rules.min_player_count = 5;
+
+        assert_eq!(
+            game_rules.validate(),
+            vec![RulesProblem {
+                path: "min_player_count".into(),
+                error: RulesError::InvalidPlayerCounts {
+                    min_player_count: 5,
+                    max_player_count: 4,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn excluded_actions_drop_out_until_the_exclusion_ends() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[5]);
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[9]);
+        *hand(&mut game_state, 1, "hand_cards") = cards(&[9]);
+        assert!(available_action(&game_state, PICK_UP).is_some());
+
+        game_state.exclude_action(PICK_UP.to_string(), EffectDuration::RestOfTurn);
+        assert!(available_action(&game_state, PICK_UP).is_none());
+        assert!(available_action(&game_state, PLAY_CARDS).is_some());
+
+        game_state.advance_player_turn();
+        assert!(available_action(&game_state, PICK_UP).is_some());
+    }
+
+    #[test]
+    fn at_least_limit_turns_down_lower_cards_but_not_wild_ones() {
+        let mut game_state = game_turn();
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[5]);
+        game_state.limit_playable_cards(
+            PlayableCardLimit::AtLeast(card(9)),
+            EffectDuration::RestOfTurn,
+        );
+        assert!(available_action(&game_state, PLAY_CARDS).is_none());
+
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[5, 2]);
+        take_action(&mut game_state, PLAY_CARDS, vec![vec![0], vec![1]]).unwrap();
+
+        assert_eq!(*communal(&mut game_state, "active_pile"), cards(&[2]));
+        assert_eq!(*hand(&mut game_state, 0, "hand_cards"), cards(&[5]));
+    }
+
+    #[test]
+    fn limits_through_the_next_turn_last_one_turn_change() {
+        let mut game_state = game_turn();
+        game_state.limit_playable_cards(
+            PlayableCardLimit::AtLeast(card(9)),
+            EffectDuration::ThroughNextTurn,
+        );
+
+        game_state.advance_player_turn();
+        assert_eq!(
+            game_state.playable_card_limits().collect::<Vec<_>>(),
+            vec![&PlayableCardLimit::AtLeast(card(9))]
+        );
+
+        game_state.advance_player_turn();
+        assert_eq!(game_state.playable_card_limits().count(), 0);
+    }
+
+    #[test]
+    fn unplayable_visible_cards_make_a_move_unavailable() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[13]);
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[4, 5]);
+
+        assert!(available_action(&game_state, PLAY_CARDS).is_none());
+        assert!(available_action(&game_state, PICK_UP).is_some());
+    }
+
+    #[test]
+    fn hidden_cards_make_a_move_available_whatever_they_are() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[13]);
+        *hand(&mut game_state, 0, "blind_cards") = cards(&[4, 5]);
+
+        assert!(available_action(&game_state, PLAY_CARDS).is_some());
+    }
+
+    #[test]
+    fn unplayable_visible_selection_is_asked_for_again() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[9]);
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[4, 14]);
+
+        take_action(&mut game_state, PLAY_CARDS, vec![vec![0], vec![1]]).unwrap();
+
+        assert_eq!(*communal(&mut game_state, "active_pile"), cards(&[9, 14]));
+        assert_eq!(*hand(&mut game_state, 0, "hand_cards"), cards(&[4]));
+        assert!(!game_state.last_card_move_failed);
+    }
+
+    #[test]
+    fn unplayable_blind_card_is_committed_and_picked_up_with_the_pile() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[13]);
+        *hand(&mut game_state, 0, "blind_cards") = cards(&[4, 14]);
+
+        // Only one selection is given, so asking again would fail
+        take_action(&mut game_state, PLAY_CARDS, vec![vec![0]]).unwrap();
+
+        assert!(game_state.last_card_move_failed);
+        assert!(communal(&mut game_state, "active_pile").is_empty());
+        assert_eq!(*hand(&mut game_state, 0, "hand_cards"), cards(&[13, 4]));
+        assert_eq!(*hand(&mut game_state, 0, "blind_cards"), cards(&[14]));
+        assert_eq!(game_state.last_played_card, None);
+    }
+
+    #[test]
+    fn playable_blind_card_is_played() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[13]);
+        *hand(&mut game_state, 0, "blind_cards") = cards(&[4, 14]);
+
+        take_action(&mut game_state, PLAY_CARDS, vec![vec![1]]).unwrap();
+
+        assert!(!game_state.last_card_move_failed);
+        assert_eq!(*communal(&mut game_state, "active_pile"), cards(&[13, 14]));
+        assert_eq!(game_state.last_played_card, Some(card(14)));
+    }
+
+    #[test]
+    fn moving_every_card_keeps_their_order() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[5, 9, 12]);
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[4]);
+
+        take_action(&mut game_state, PICK_UP, vec![]).unwrap();
+
+        assert_eq!(
+            *hand(&mut game_state, 0, "hand_cards"),
+            cards(&[4, 5, 9, 12])
+        );
+        assert!(communal(&mut game_state, "active_pile").is_empty());
+    }
+
+    #[test]
+    fn drawing_takes_cards_off_the_top_until_the_destination_is_full() {
+        let mut game_state = game_turn();
+        game_state.end_phase();
+        *communal(&mut game_state, "draw_pile") = cards(&[5, 6, 7, 8]);
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[4]);
+
+        take_action(&mut game_state, DRAW, vec![]).unwrap();
+
+        assert_eq!(*hand(&mut game_state, 0, "hand_cards"), cards(&[4, 8, 7]));
+        assert_eq!(*communal(&mut game_state, "draw_pile"), cards(&[5, 6]));
+    }
+
+    #[test]
+    fn drawing_stops_when_the_source_runs_out() {
+        let mut game_state = game_turn();
+        game_state.end_phase();
+        *communal(&mut game_state, "draw_pile") = cards(&[5]);
+
+        take_action(&mut game_state, DRAW, vec![]).unwrap();
+
+        assert_eq!(*hand(&mut game_state, 0, "hand_cards"), cards(&[5]));
+        assert!(communal(&mut game_state, "draw_pile").is_empty());
+    }
+
+    #[test]
+    fn consequences_are_checked_before_any_of_them_run() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[9]);
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[10, 4]);
+
+        take_action(&mut game_state, PLAY_CARDS, vec![vec![0]]).unwrap();
+
+        // Discarding the pile takes the ten away, but playing again still
+        // applies
+        assert!(communal(&mut game_state, "active_pile").is_empty());
+        assert_eq!(*communal(&mut game_state, "discard_pile"), cards(&[9, 10]));
+        game_state.advance_player_turn();
+        assert_eq!(game_state.player_index_at_offset(0), 0);
+    }
+
+    #[test]
+    fn consequences_can_act_on_other_players() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[9]);
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[3, 4]);
+        *hand(&mut game_state, 1, "hand_cards") = cards(&[5]);
+
+        take_action(&mut game_state, PLAY_CARDS, vec![vec![0]]).unwrap();
+
+        assert_eq!(*hand(&mut game_state, 1, "hand_cards"), cards(&[5, 9, 3]));
+        game_state.advance_player_turn();
+        assert_eq!(game_state.player_index_at_offset(0), 0);
+    }
+
+    #[test]
+    fn highest_priority_consequence_wins_a_conflict() {
+        let action: Action = serde_yaml::from_str(
+            r#"
+description: "Play again or skip"
+verb: EndPhase
+consequences:
+  - description: "Skip the next player"
+    verb:
+      MoveNextTurn:
+        offset_from_current_player: 2
+  - description: "Play again"
+    priority: 1
+    verb:
+      MoveNextTurn:
+        offset_from_current_player: 0
+  - description: "Also play again"
+    priority: 1
+    verb:
+      MoveNextTurn:
+        offset_from_current_player: 1
+"#,
         )
+        .unwrap();
+        let mut game_state = game_turn();
+
+        action
+            .execute(&mut game_state, &mut ScriptedController::new(vec![]))
+            .unwrap();
+
+        let executed = game_state
+            .game_log
+            .entries
+            .iter()
+            .filter_map(|log_entry| match log_entry {
+                LogEntry::ActionExecuted { description, .. } => Some(description.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            executed,
+            [
+                "Play again or skip",
+                "Play again",
+                "Also play again",
+                "Skip the next player"
+            ]
+        );
+        game_state.advance_player_turn();
+        assert_eq!(game_state.player_index_at_offset(0), 0);
+    }
+
+    #[test]
+    fn cards_played_onto_a_pile_are_public_even_once_covered() {
+        let mut game_state = game_turn();
+        *communal(&mut game_state, "active_pile") = cards(&[4]);
+        *hand(&mut game_state, 0, "hand_cards") = cards(&[5, 5, 9]);
+        *communal(&mut game_state, "draw_pile") = cards(&[6, 7]);
+
+        take_action(&mut game_state, PLAY_CARDS, vec![vec![0, 1]]).unwrap();
+        take_action(&mut game_state, DRAW, vec![]).unwrap();
+
+        let active_pile = communal(&mut game_state, "active_pile");
+        assert_eq!(
+            active_pile
+                .iter()
+                .map(|card| card.public)
+                .collect::<Vec<bool>>(),
+            [false, true, true]
+        );
+        let hand_cards = hand(&mut game_state, 0, "hand_cards");
+        assert_eq!(*hand_cards, cards(&[9, 7, 6]));
+        assert!(hand_cards.iter().all(|card| !card.public));
     }
 }
@@ -1,50 +1,442 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::mem;
+use std::path::PathBuf;
+use std::rc::Rc;
 
-use crate::card_deck::{CardGroup, Deck};
-use crate::game_rules::{Action, GameRules};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+use crate::active_effects::{ActiveEffects, EffectDuration};
+use crate::card_deck::{Card, CardGroup, CardRank, CardValue, Deck};
+use crate::game_error::{GameError, RulesError};
+use crate::game_log::{GameLog, LogEntry, LogWriter};
+use crate::game_rules::{GameRules, PlayableCardLimit};
 use crate::player::Player;
-use crate::user_input;
+use crate::player_controller::{ActionOption, CardChoice, PlayerController};
+use crate::snapshot::{self, GameSnapshot};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameResult {
+    // Player indices in the order they went out. The last one is whoever was
+    // left when the game ended
+    pub finishing_order: Vec<usize>,
+    pub turn_count: usize,
+}
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GameState {
-    pub communal_cards: HashMap<String, CardGroup>,
+    // The game as it was before the current player picked the action they're
+    // taking, and how long the log was then
+    #[serde(skip)]
+    action_start: Option<(Box<GameState>, usize)>,
+    // When set the game is saved here before each decision
+    #[serde(skip)]
+    pub autosave_path: Option<PathBuf>,
+    pub communal_cards: BTreeMap<String, CardGroup>,
+    // How many decisions have been made so far, counted for decision_limit
+    #[serde(skip)]
+    decision_count: usize,
+    // When set the game stops with an error once players have been asked
+    // for this many decisions. Unlike the turn limit it stops a turn that
+    // never ends
+    #[serde(skip)]
+    pub decision_limit: Option<usize>,
     deck: Deck,
-    game_rules: GameRules,
+    excluded_actions: ActiveEffects<String>,
+    pub finishing_order: Vec<usize>,
+    #[serde(skip)]
+    pub game_log: GameLog,
+    // Shared with every copy of the game, eg. undo states
+    game_rules: Rc<GameRules>,
+    #[serde(skip)]
+    pub log_path: Option<PathBuf>,
+    // Writes the log to log_path as the game goes. Copies of the game, eg.
+    // undo states, don't have one
+    #[serde(skip)]
+    log_writer: Option<Rc<LogWriter>>,
+    // Whether the cards picked in the last player chosen card move turned out
+    // not to meet its card conditions once they were turned over
+    #[serde(default)]
+    pub last_card_move_failed: bool,
+    pub last_played_card: Option<Card>,
+    next_turn_offset: Option<usize>,
+    playable_card_limits: ActiveEffects<PlayableCardLimit>,
     player_turn_index: usize,
     pub players: Vec<Player>,
+    // Every random choice in a game comes from this, so the same seed and the
+    // same player choices always play out the same way
+    rng: Pcg64,
+    pub seed: u64,
     pub turn_count: usize,
+    turn_in_progress: bool,
+    // When set the game stops with an error once this many turns have passed
+    #[serde(skip)]
+    pub turn_limit: Option<usize>,
+    pub turn_type_index: Option<usize>,
+    pub turn_phase_index: usize,
+    // Whether players can take back actions until their turn passes
+    #[serde(default)]
+    pub undo_allowed: bool,
+    // The game as it was before each action taken so far this turn
+    #[serde(skip)]
+    undo_states: Vec<GameState>,
 }
 
 impl GameState {
-    pub fn new(game_rules: GameRules, mut players: Vec<Player>) -> Self {
-        for mut player in &mut players {
+    pub fn new(game_rules: GameRules, mut players: Vec<Player>, seed: u64) -> Self {
+        for player in &mut players {
             player.hand = game_rules.player_hand.clone();
         }
 
-        // TODO: Build deck from game rules
-        let deck: Deck = Default::default();
-        let deck = deck.shuffle();
+        let deck = Deck::from_specification(&game_rules.deck);
+        let game_log = GameLog {
+            seed,
+            player_names: players.iter().map(|player| player.name.clone()).collect(),
+            player_controllers: players.iter().map(|player| player.controller).collect(),
+            rules_fingerprint: game_rules.fingerprint(),
+            ..Default::default()
+        };
 
         return GameState {
+            action_start: None,
+            autosave_path: None,
             communal_cards: game_rules.communal_cards.clone(),
+            decision_count: 0,
+            decision_limit: None,
             deck,
-            game_rules,
+            excluded_actions: Default::default(),
+            finishing_order: vec![],
+            game_log,
+            game_rules: Rc::new(game_rules),
+            log_path: None,
+            log_writer: None,
+            last_card_move_failed: false,
+            last_played_card: None,
+            next_turn_offset: None,
+            playable_card_limits: Default::default(),
             player_turn_index: 0,
             players,
+            rng: Pcg64::seed_from_u64(seed),
+            seed,
             turn_count: 0,
+            turn_in_progress: false,
+            turn_type_index: None,
+            turn_phase_index: 0,
+            turn_limit: None,
+            undo_allowed: false,
+            undo_states: vec![],
         };
     }
 
+    pub fn player_finished(&self, player_index: usize) -> bool {
+        self.finishing_order.contains(&player_index)
+    }
+
+    // Counts seats from the current player, skipping players who have
+    // finished. An offset of 0 is always the current player
+    pub fn player_index_at_offset(&self, offset: usize) -> usize {
+        let player_count = self.players.len();
+        if (0..player_count).all(|player_index| self.player_finished(player_index)) {
+            return (self.player_turn_index + offset) % player_count;
+        }
+
+        let mut player_index = self.player_turn_index;
+        let mut remaining_offset = offset;
+        while remaining_offset > 0 {
+            player_index = (player_index + 1) % player_count;
+            if !self.player_finished(player_index) {
+                remaining_offset -= 1;
+            }
+        }
+
+        return player_index;
+    }
+
     pub fn offset_from_current_player_mut(&mut self, offset: usize) -> Option<&mut Player> {
-        let player_index = (self.player_turn_index + offset) % self.players.len();
+        let player_index = self.player_index_at_offset(offset);
         return self.players.get_mut(player_index);
     }
 
     pub fn offset_from_current_player(&self, offset: usize) -> Option<&Player> {
-        let player_index = (self.player_turn_index + offset) % self.players.len();
+        let player_index = self.player_index_at_offset(offset);
         return self.players.get(player_index);
     }
 
+    pub fn game_rules(&self) -> &GameRules {
+        &self.game_rules
+    }
+
+    // Changes whenever anything about the game does, eg. to check a replay
+    // ended up in the same place as the original game
+    pub fn fingerprint(&self) -> String {
+        snapshot::fingerprint(self)
+    }
+
+    // Opens the log file, writing out anything logged before the game
+    // started. A game that's already logging keeps going with the same file
+    fn start_log(&mut self) {
+        self.game_log.undo_allowed = self.undo_allowed;
+        if self.log_writer.is_some() {
+            return;
+        }
+
+        if let Some(log_path) = &self.log_path {
+            match LogWriter::create(log_path, &self.game_log) {
+                Ok(log_writer) => self.log_writer = Some(Rc::new(log_writer)),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+    }
+
+    // Entries are written as they're logged. Logging stops for the rest of
+    // the game if writing fails, rather than reporting it over and over
+    fn log(&mut self, log_entry: LogEntry) {
+        if let Some(log_writer) = &self.log_writer {
+            if let Err(error) = log_writer.write_entry(&log_entry) {
+                eprintln!("{}", error);
+                self.log_writer = None;
+            }
+        }
+
+        self.game_log.entries.push(log_entry);
+    }
+
+    fn finish_log(&mut self) {
+        if let Some(log_writer) = self.log_writer.take() {
+            let state_fingerprint = self.fingerprint();
+            if let Err(error) = log_writer.write_end(&state_fingerprint) {
+                eprintln!("{}", error);
+            }
+            self.game_log.state_fingerprint = Some(state_fingerprint);
+        }
+    }
+
+    // Asks the player which option they want until they pick one that exists
+    fn choose_action(
+        &mut self,
+        controller: &mut dyn PlayerController,
+        options: &[ActionOption],
+    ) -> Result<usize, GameError> {
+        let player_index = self.player_turn_index;
+        loop {
+            self.count_decision()?;
+            let action_index = controller.choose_action(self, player_index, options)?;
+            self.log(LogEntry::ActionChosen {
+                player_index,
+                action_index,
+            });
+
+            if action_index < options.len() {
+                return Ok(action_index);
+            }
+
+            controller.show_message(self, player_index, "Invalid action selection");
+        }
+    }
+
+    // Asks the player for cards until they pick a selection the choice allows
+    pub fn choose_cards(
+        &mut self,
+        controller: &mut dyn PlayerController,
+        card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError> {
+        let player_index = self.player_turn_index;
+        loop {
+            self.count_decision()?;
+            let card_indices = controller.choose_cards(self, player_index, card_choice)?;
+            self.log(LogEntry::CardsChosen {
+                player_index,
+                card_indices: card_indices.clone(),
+            });
+
+            match card_choice.selection_problem(&card_indices, self)? {
+                None => return Ok(card_indices),
+                Some(selection_problem) => controller.show_message(
+                    self,
+                    player_index,
+                    &format!("Invalid selection, {}. Try again", selection_problem),
+                ),
+            }
+        }
+    }
+
+    fn count_decision(&mut self) -> Result<(), GameError> {
+        if let Some(decision_limit) = self.decision_limit {
+            if self.decision_count >= decision_limit {
+                return Err(GameError::DecisionLimitReached(decision_limit));
+            }
+        }
+
+        self.decision_count += 1;
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_states.is_empty()
+    }
+
+    // A copy of the game as it is now, without its history: the log, the undo
+    // states and where the current action started
+    fn copy_without_history(&mut self) -> GameState {
+        let action_start = self.action_start.take();
+        let game_log = mem::take(&mut self.game_log);
+        let log_writer = self.log_writer.take();
+        let undo_states = mem::take(&mut self.undo_states);
+
+        let copy = self.clone();
+
+        self.action_start = action_start;
+        self.game_log = game_log;
+        self.log_writer = log_writer;
+        self.undo_states = undo_states;
+
+        return copy;
+    }
+
+    // Remembers the game as it is now so the next action can be undone. The
+    // log and save settings aren't part of what gets undone
+    fn push_undo_state(&mut self) {
+        if !self.undo_allowed {
+            return;
+        }
+
+        let undo_state = self.copy_without_history();
+        self.undo_states.push(undo_state);
+    }
+
+    // The game as it was before the current player picked the action they're
+    // in the middle of, and every choice they've made since. Bots play the
+    // game out from there to weigh up a choice, since the game can't be
+    // picked up part way through an action
+    pub fn action_start(&self) -> Option<(&GameState, Vec<LogEntry>)> {
+        let (game_state, log_length) = self.action_start.as_ref()?;
+        let choices = self.game_log.entries[*log_length..]
+            .iter()
+            .filter(|log_entry| log_entry.player_choice())
+            .cloned()
+            .collect();
+
+        return Some((game_state, choices));
+    }
+
+    // Shuffles every card the viewer doesn't know in among the places they
+    // can't see, leaving the cards they can see and the ones everyone has
+    // seen where they are. Bots use it to guess at the hidden cards without
+    // peeking
+    pub fn shuffle_hidden_cards<R: Rng>(&mut self, viewer_index: usize, rng: &mut R) {
+        let communal_card_groups = self
+            .communal_cards
+            .values_mut()
+            .map(|card_group| (card_group, false));
+        let player_card_groups =
+            self.players
+                .iter_mut()
+                .enumerate()
+                .flat_map(|(player_index, player)| {
+                    player
+                        .hand
+                        .values_mut()
+                        .map(move |card_group| (card_group, player_index == viewer_index))
+                });
+
+        let mut hidden_cards: Vec<&mut Card> = self.deck.cards.iter_mut().collect();
+        for (card_group, seen_by_owner) in communal_card_groups.chain(player_card_groups) {
+            let face_up = card_group
+                .visible_cards(seen_by_owner)
+                .iter()
+                .map(Option::is_some)
+                .collect::<Vec<bool>>();
+            for (card, face_up) in card_group.cards.iter_mut().zip(face_up) {
+                if !face_up && !card.public {
+                    hidden_cards.push(card);
+                }
+            }
+        }
+
+        let mut shuffled_cards = hidden_cards
+            .iter()
+            .map(|card| (**card).clone())
+            .collect::<Vec<Card>>();
+        shuffled_cards.shuffle(rng);
+        for (card, shuffled_card) in hidden_cards.into_iter().zip(shuffled_cards) {
+            *card = shuffled_card;
+        }
+    }
+
+    // Every card the player can see, wherever it is
+    fn cards_seen_by(&self, player_index: usize) -> Vec<Card> {
+        let communal_cards = self
+            .communal_cards
+            .values()
+            .flat_map(|card_group| card_group.visible_cards(false));
+        let player_cards = self.players.iter().enumerate().flat_map(|(index, player)| {
+            player
+                .hand
+                .values()
+                .flat_map(move |card_group| card_group.visible_cards(index == player_index))
+        });
+
+        return communal_cards
+            .chain(player_cards)
+            .flatten()
+            .cloned()
+            .collect();
+    }
+
+    // Whether the player can see any card now that they couldn't before, eg.
+    // one they drew or a blind card they played
+    fn cards_revealed_to(&self, player_index: usize, cards_seen_before: &[Card]) -> bool {
+        let mut cards_seen_before = cards_seen_before.to_vec();
+        for card in self.cards_seen_by(player_index) {
+            match cards_seen_before
+                .iter()
+                .position(|seen_card| *seen_card == card)
+            {
+                Some(seen_card_index) => {
+                    cards_seen_before.swap_remove(seen_card_index);
+                }
+                None => return true,
+            }
+        }
+
+        return false;
+    }
+
+    // Puts the game back to how it was before the last action this turn.
+    // Returns whether there was anything to undo
+    pub fn undo(&mut self) -> bool {
+        if let Some(mut undo_state) = self.undo_states.pop() {
+            undo_state.autosave_path = self.autosave_path.take();
+            undo_state.decision_count = self.decision_count;
+            undo_state.game_log = mem::take(&mut self.game_log);
+            undo_state.log_path = self.log_path.take();
+            undo_state.log_writer = self.log_writer.take();
+            undo_state.undo_states = mem::take(&mut self.undo_states);
+            *self = undo_state;
+
+            return true;
+        }
+
+        return false;
+    }
+
+    pub fn log_action_executed(&mut self, description: &str) {
+        self.log(LogEntry::ActionExecuted {
+            player_index: self.player_turn_index,
+            description: description.to_string(),
+        });
+    }
+
+    pub fn card_value(&self, rank: &CardRank) -> Result<&CardValue, GameError> {
+        if let Some(card_description) = self.game_rules.cards.get(rank) {
+            Ok(&card_description.value)
+        } else {
+            Err(RulesError::MissingCardDescription(rank.clone()).into())
+        }
+    }
+
     fn maybe_deal_card_to_card_group(
         deck: &mut Deck,
         card_group: &mut CardGroup,
@@ -61,7 +453,7 @@ impl GameState {
 
         let mut deck_empty = false;
         if let Some(card) = deck.cards.pop() {
-            card_group.cards.push(card);
+            card_group.add_cards([card]);
         } else {
             deck_empty = true;
         }
@@ -74,9 +466,11 @@ impl GameState {
     }
 
     pub fn deal(&mut self) {
+        self.deck.shuffle(&mut self.rng);
+
         let player_count = self.players.len();
 
-        for (player_hand_name, _) in self.game_rules.player_hand.iter() {
+        for player_hand_name in self.game_rules.player_hand.keys() {
             let mut hand_at_initial_deal_count_for_all_players = false;
 
             for player_index in (0..player_count).cycle() {
@@ -90,10 +484,13 @@ impl GameState {
                     .players
                     .get_mut(player_index)
                     .expect("Error getting a player by index");
-                let player_hand = player.hand.get_mut(player_hand_name).expect(&format!(
-                    "Player {} is missing hand {}",
-                    player.name, player_hand_name
-                ));
+                let player_name = &player.name;
+                let player_hand = player.hand.get_mut(player_hand_name).unwrap_or_else(|| {
+                    panic!(
+                        "Player {} is missing hand {}",
+                        player_name, player_hand_name
+                    )
+                });
 
                 let (maybe_card_group_full, deck_empty) =
                     GameState::maybe_deal_card_to_card_group(&mut self.deck, player_hand);
@@ -114,69 +511,553 @@ impl GameState {
             }
         }
 
-        for (_, communal_card_group) in self.communal_cards.iter_mut() {
-            let (maybe_card_group_full, deck_empty) =
-                GameState::maybe_deal_card_to_card_group(&mut self.deck, communal_card_group);
+        let communal_card_group_names =
+            self.communal_cards.keys().cloned().collect::<Vec<String>>();
 
-            // If there aren't enough cards to finish dealing I abruptly
-            // return here. Is this behavior correct? Should it be configurable?
-            if deck_empty {
-                return;
-            }
+        for communal_card_group_name in communal_card_group_names.iter() {
+            let communal_card_group = self
+                .communal_cards
+                .get_mut(communal_card_group_name)
+                .expect("Error getting a communal card group by name");
 
-            if let Some(card_group_full) = maybe_card_group_full {
-                if card_group_full {
-                    continue;
+            while let Some(false) = communal_card_group.at_or_over_initial_deal_size() {
+                if let Some(card) = self.deck.cards.pop() {
+                    communal_card_group.add_cards([card]);
+                } else {
+                    return;
                 }
             }
         }
+
+        // Whatever is left of the deck goes to the communal groups without an
+        // initial deal count, eg. the draw pile
+        let rest_of_deck_card_group_names = communal_card_group_names
+            .iter()
+            .filter(|name| self.communal_cards[*name].initial_deal_count.is_none())
+            .collect::<Vec<&String>>();
+        if rest_of_deck_card_group_names.is_empty() {
+            return;
+        }
+
+        for (i, card) in self.deck.cards.drain(..).enumerate() {
+            let communal_card_group_name =
+                rest_of_deck_card_group_names[i % rest_of_deck_card_group_names.len()];
+            self.communal_cards
+                .get_mut(communal_card_group_name)
+                .expect("Error getting a communal card group by name")
+                .add_cards([card]);
+        }
+    }
+
+    pub fn exclude_action(&mut self, action_description: String, duration: EffectDuration) {
+        self.excluded_actions.add(action_description, duration);
     }
 
-    pub fn player_on_turn(&mut self) -> &mut Player {
-        return &mut self.players[self.player_turn_index];
+    pub fn action_excluded(&self, action_description: &str) -> bool {
+        self.excluded_actions
+            .iter()
+            .any(|excluded_action| excluded_action == action_description)
     }
 
+    pub fn limit_playable_cards(
+        &mut self,
+        playable_card_limit: PlayableCardLimit,
+        duration: EffectDuration,
+    ) {
+        self.playable_card_limits.add(playable_card_limit, duration);
+    }
+
+    pub fn playable_card_limits(&self) -> impl Iterator<Item = &PlayableCardLimit> {
+        self.playable_card_limits.iter()
+    }
+
+    // Sets who plays once this turn is over, relative to the current player.
+    // 0 means the current player goes again and 2 skips the next player
+    pub fn move_next_turn(&mut self, offset_from_current_player: usize) {
+        self.next_turn_offset = Some(offset_from_current_player);
+    }
+
+    // The turn count goes up each time play passes the first seat, including
+    // when a turn move jumps over it. Finished players are skipped
     pub fn advance_player_turn(&mut self) {
-        self.player_turn_index = (self.player_turn_index + 1) % self.players.len();
+        let mut offset = self.next_turn_offset.take().unwrap_or(1);
+        if offset == 0 && self.player_finished(self.player_turn_index) {
+            offset = 1;
+        }
 
-        if self.player_turn_index == 0 {
-            self.turn_count = self.turn_count + 1
+        let player_count = self.players.len();
+        let mut remaining_offset = offset;
+        while remaining_offset > 0 && self.finishing_order.len() < player_count {
+            self.player_turn_index = (self.player_turn_index + 1) % player_count;
+            if self.player_turn_index == 0 {
+                self.turn_count += 1;
+            }
+
+            if !self.player_finished(self.player_turn_index) {
+                remaining_offset -= 1;
+            }
         }
+
+        self.excluded_actions.end_turn();
+        self.playable_card_limits.end_turn();
+        self.turn_in_progress = false;
+        self.undo_states.clear();
     }
 
-    pub fn play_game(&mut self) -> Result<(), String> {
+    // Checks every player still in the game against the rules' finished
+    // conditions, as if it were their turn
+    fn update_finished_players(&mut self) -> Result<(), GameError> {
+        let player_turn_index = self.player_turn_index;
+        let mut newly_finished_players = vec![];
+        let mut result = Ok(());
+
+        for player_index in 0..self.players.len() {
+            if self.player_finished(player_index) {
+                continue;
+            }
+
+            self.player_turn_index = player_index;
+            match self.game_rules.current_player_finished(self) {
+                Ok(true) => newly_finished_players.push(player_index),
+                Ok(false) => {}
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+
+        self.player_turn_index = player_turn_index;
+        self.finishing_order.extend(newly_finished_players);
+
+        return result;
+    }
+
+    fn game_result(&self) -> GameResult {
+        let mut finishing_order = self.finishing_order.clone();
+        for player_index in 0..self.players.len() {
+            if !self.player_finished(player_index) {
+                finishing_order.push(player_index);
+            }
+        }
+
+        return GameResult {
+            finishing_order,
+            turn_count: self.turn_count,
+        };
+    }
+
+    pub fn start_turn(&mut self) -> Result<(), GameError> {
+        self.turn_type_index = self.game_rules.select_turn_type(self)?;
+        self.turn_phase_index = 0;
+        self.turn_in_progress = true;
+
+        Ok(())
+    }
+
+    pub fn end_phase(&mut self) {
+        self.turn_phase_index += 1;
+
+        self.excluded_actions.end_phase();
+        self.playable_card_limits.end_phase();
+    }
+
+    // A turn is over once every phase of its turn type has ended. Turns
+    // without a matching turn type are over immediately
+    pub fn turn_over(&self) -> bool {
+        self.turn_phase_index >= self.game_rules.turn_phases(self.turn_type_index).len()
+    }
+
+    // The log is written as the game goes, and finished however the game
+    // ends, including when a player stops answering part way through. Each
+    // player's decisions are made by the controller at their seat
+    pub fn play_game(
+        &mut self,
+        controllers: &mut [Box<dyn PlayerController>],
+    ) -> Result<GameResult, GameError> {
+        if controllers.len() != self.players.len() {
+            return Err(GameError::Invariant(format!(
+                "Every player needs a controller. Players: {}, controllers: {}",
+                self.players.len(),
+                controllers.len(),
+            )));
+        }
+
+        self.start_log();
+        let result = self.play_until_over(controllers);
+        self.finish_log();
+
+        return result;
+    }
+
+    fn play_until_over(
+        &mut self,
+        controllers: &mut [Box<dyn PlayerController>],
+    ) -> Result<GameResult, GameError> {
+        // A resumed game picks up part way through a turn
+        if !self.turn_in_progress {
+            self.start_turn()?;
+        }
+
         loop {
-            println!("");
-            println!("Communal cards: {:?}", self.communal_cards);
-            let player = self.player_on_turn();
-            println!("Your cards: {:?}", player.hand);
+            if let Some(autosave_path) = &self.autosave_path {
+                if let Err(error) = GameSnapshot::new(self).save(autosave_path) {
+                    eprintln!("{}", error);
+                }
+            }
+
+            let player_index = self.player_turn_index;
+            let controller = controllers[player_index].as_mut();
+
+            self.action_start = None;
+            if controller.wants_action_start() {
+                let action_start = self.copy_without_history();
+                self.action_start = Some((Box::new(action_start), self.game_log.entries.len()));
+            }
 
             // This clone satisfies the borrowck and allows me to mutably pass self to
             // Action.excute
-            let game_rules = self.game_rules.clone();
-            let available_actions = game_rules.available_actions(self)?;
-            if let Some(selected_action) = user_input::select_action(available_actions) {
-                selected_action.execute(self)?;
+            let game_rules = Rc::clone(&self.game_rules);
+
+            let mut options = game_rules
+                .available_actions(self)?
+                .into_iter()
+                .map(ActionOption::Action)
+                .collect::<Vec<ActionOption>>();
+
+            // Phases where nothing can be done are skipped
+            if options.is_empty() {
+                self.end_phase();
+            } else {
+                if self.can_undo() {
+                    options.push(ActionOption::Undo);
+                }
+
+                // A lone action is taken without asking, but still counts as
+                // a decision so a loop of them stops at the decision limit
+                let option_index = if options.len() == 1 {
+                    self.count_decision()?;
+                    0
+                } else {
+                    self.choose_action(controller, &options)?
+                };
+
+                match options[option_index] {
+                    ActionOption::Action(selected_action) => {
+                        self.push_undo_state();
+                        let cards_seen_before = if self.can_undo() {
+                            self.cards_seen_by(player_index)
+                        } else {
+                            vec![]
+                        };
+
+                        selected_action.execute(self, controller)?;
+
+                        // Taking back an action after seeing what it turned
+                        // over would let a player peek and try again
+                        if self.can_undo()
+                            && self.cards_revealed_to(player_index, &cards_seen_before)
+                        {
+                            self.undo_states.clear();
+                        }
+                    }
+                    ActionOption::Undo => {
+                        self.undo();
+                        continue;
+                    }
+                    // Only offered once the turn is over
+                    ActionOption::EndTurn => {}
+                }
             }
 
-            // TODO:
-            //
-            //  Need to implement actions and conditions
-            //  then to implement game flow into actions, eg advancing turn phase and next turn
-            //
-            // How do I know what cards to eval for consequences?
-            // What was "just played"
-            // After user plays card re-eval consequences to handle if eg the player can play again
+            if self.turn_over() {
+                // The last chance to take something back before play passes
+                if self.can_undo() {
+                    let options = [ActionOption::EndTurn, ActionOption::Undo];
+                    if options[self.choose_action(controller, &options)?] == ActionOption::Undo {
+                        self.undo();
+                        continue;
+                    }
+                }
+
+                self.update_finished_players()?;
+                if self.game_rules.game_over(self)?
+                    || self.finishing_order.len() == self.players.len()
+                {
+                    return Ok(self.game_result());
+                }
+
+                self.advance_player_turn();
+                if let Some(turn_limit) = self.turn_limit {
+                    if self.turn_count >= turn_limit {
+                        return Err(GameError::TurnLimitReached(turn_limit));
+                    }
+                }
 
-            // play_turn(&mut game_state);
+                self.start_turn()?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_deck::Suit;
+    use crate::game_error::InputError;
+    use crate::game_rules::poo_head_rules;
+    use crate::player_controller::ScriptedController;
+
+    fn game_with_players(player_count: usize) -> GameState {
+        let players = (0..player_count)
+            .map(|player_index| Player::from_spec(&format!("P{}", player_index)).unwrap())
+            .collect();
+
+        return GameState::new(poo_head_rules(), players, 0);
+    }
+
+    // Moves play on from the given seat and returns the next seat and how
+    // many times the first seat was passed
+    fn next_turn(
+        game_state: &mut GameState,
+        player_turn_index: usize,
+        offset: Option<usize>,
+    ) -> (usize, usize) {
+        game_state.player_turn_index = player_turn_index;
+        let turn_count = game_state.turn_count;
+        if let Some(offset) = offset {
+            game_state.move_next_turn(offset);
+        }
+
+        game_state.advance_player_turn();
+
+        return (
+            game_state.player_turn_index,
+            game_state.turn_count - turn_count,
+        );
+    }
+
+    #[test]
+    fn play_passes_to_the_next_seat_by_default() {
+        let mut game_state = game_with_players(3);
+
+        assert_eq!(next_turn(&mut game_state, 0, None), (1, 0));
+        assert_eq!(next_turn(&mut game_state, 2, None), (0, 1));
+    }
+
+    #[test]
+    fn offset_zero_plays_again() {
+        let mut game_state = game_with_players(3);
+
+        assert_eq!(next_turn(&mut game_state, 0, Some(0)), (0, 0));
+        assert_eq!(next_turn(&mut game_state, 2, Some(0)), (2, 0));
+    }
+
+    #[test]
+    fn offset_two_skips_a_player_and_counts_passing_the_first_seat() {
+        let mut game_state = game_with_players(3);
+
+        assert_eq!(next_turn(&mut game_state, 0, Some(2)), (2, 0));
+        assert_eq!(next_turn(&mut game_state, 1, Some(2)), (0, 1));
+        assert_eq!(next_turn(&mut game_state, 2, Some(2)), (1, 1));
+    }
+
+    #[test]
+    fn offset_wrapping_all_the_way_round_counts_once() {
+        let mut game_state = game_with_players(2);
+
+        assert_eq!(next_turn(&mut game_state, 0, Some(2)), (0, 1));
+        assert_eq!(next_turn(&mut game_state, 1, Some(2)), (1, 1));
+    }
+
+    #[test]
+    fn turn_moves_are_used_once() {
+        let mut game_state = game_with_players(3);
+
+        assert_eq!(next_turn(&mut game_state, 0, Some(0)), (0, 0));
+        assert_eq!(next_turn(&mut game_state, 0, None), (1, 0));
+    }
+
+    #[test]
+    fn finished_players_are_skipped() {
+        let mut game_state = game_with_players(4);
+        game_state.finishing_order = vec![0, 2];
+
+        assert_eq!(next_turn(&mut game_state, 1, None), (3, 0));
+        assert_eq!(next_turn(&mut game_state, 3, None), (1, 1));
+        assert_eq!(next_turn(&mut game_state, 1, Some(2)), (1, 1));
+        assert_eq!(game_state.player_index_at_offset(1), 3);
+    }
+
+    #[test]
+    fn finished_player_cant_play_again() {
+        let mut game_state = game_with_players(3);
+        game_state.finishing_order = vec![1];
+
+        assert_eq!(next_turn(&mut game_state, 1, Some(0)), (2, 0));
+    }
+
+    fn cards(ranks: &[usize]) -> Vec<Card> {
+        ranks
+            .iter()
+            .map(|rank| Card::new(Suit::Heart, CardRank::from_usize(*rank)))
+            .collect()
+    }
+
+    // A game past setup where the first player has the given hand and blind
+    // cards, with a four on the active pile. The second player has a card so
+    // the game isn't over
+    fn undoable_game(
+        hand_cards: &[usize],
+        blind_cards: &[usize],
+        draw_pile: &[usize],
+    ) -> GameState {
+        let mut game_state = game_with_players(2);
+        game_state.turn_count = 1;
+        game_state.undo_allowed = true;
+        game_state
+            .communal_cards
+            .get_mut("active_pile")
+            .unwrap()
+            .cards = cards(&[4]);
+        game_state
+            .communal_cards
+            .get_mut("draw_pile")
+            .unwrap()
+            .cards = cards(draw_pile);
+        let hand = &mut game_state.players[0].hand;
+        hand.get_mut("hand_cards").unwrap().cards = cards(hand_cards);
+        hand.get_mut("blind_cards").unwrap().cards = cards(blind_cards);
+        game_state.players[1]
+            .hand
+            .get_mut("hand_cards")
+            .unwrap()
+            .cards = cards(&[14]);
+
+        return game_state;
+    }
+
+    // Plays the first card the first player has, then picks the given
+    // options, the last of them undo if they're still offered it
+    fn play_then_try_to_undo(
+        game_state: &mut GameState,
+        action_indices: &[usize],
+    ) -> Result<GameResult, GameError> {
+        let mut choices = vec![
+            LogEntry::ActionChosen {
+                player_index: 0,
+                action_index: 1,
+            },
+            LogEntry::CardsChosen {
+                player_index: 0,
+                card_indices: vec![0],
+            },
+        ];
+        for action_index in action_indices {
+            choices.push(LogEntry::ActionChosen {
+                player_index: 0,
+                action_index: *action_index,
+            });
+        }
+        let mut controllers: Vec<Box<dyn PlayerController>> = vec![
+            Box::new(ScriptedController::new(choices)),
+            Box::new(ScriptedController::new(vec![])),
+        ];
+
+        return game_state.play_game(&mut controllers);
+    }
+
+    fn active_pile(game_state: &GameState) -> &[Card] {
+        &game_state.communal_cards["active_pile"].cards
+    }
+
+    #[test]
+    fn actions_that_reveal_nothing_can_be_undone() {
+        let mut game_state = undoable_game(&[5, 9], &[], &[]);
+
+        let result = play_then_try_to_undo(&mut game_state, &[1]);
+
+        assert!(matches!(
+            result,
+            Err(GameError::Input(InputError::EndOfInput))
+        ));
+        assert_eq!(game_state.player_turn_index, 0);
+        assert_eq!(active_pile(&game_state), &cards(&[4])[..]);
+    }
+
+    #[test]
+    fn drawing_cards_clears_the_undo_states() {
+        let mut game_state = undoable_game(&[5, 9], &[], &[6, 7]);
+
+        // Drawing is offered along with undo
+        let result = play_then_try_to_undo(&mut game_state, &[0, 1]);
+
+        assert!(matches!(
+            result,
+            Err(GameError::Input(InputError::EndOfInput))
+        ));
+        assert_eq!(game_state.player_turn_index, 1);
+        assert_eq!(active_pile(&game_state), &cards(&[4, 5])[..]);
+    }
+
+    #[test]
+    fn playing_a_blind_card_clears_the_undo_states() {
+        let mut game_state = undoable_game(&[], &[5], &[]);
+
+        let game_result = play_then_try_to_undo(&mut game_state, &[1]).unwrap();
+
+        assert_eq!(game_result.finishing_order, vec![0, 1]);
+        assert_eq!(active_pile(&game_state), &cards(&[4, 5])[..]);
+    }
+
+    #[test]
+    fn shuffling_hidden_cards_leaves_cards_everyone_has_seen() {
+        let mut game_state = undoable_game(&[5, 9], &[6, 7, 8], &[10, 11, 12]);
+        // Nothing was dealt, so the deck still has every card
+        game_state.deck.cards.clear();
+        let opponent_hand = &mut game_state.players[1].hand;
+        let mut picked_up_cards = cards(&[2, 3]);
+        for card in picked_up_cards.iter_mut() {
+            card.public = true;
+        }
+        opponent_hand.get_mut("hand_cards").unwrap().cards = picked_up_cards;
+        opponent_hand
+            .get_mut("hand_cards")
+            .unwrap()
+            .cards
+            .extend(cards(&[13]));
+
+        let hidden_cards = |game_state: &GameState| {
+            let mut hidden_cards = vec![game_state.players[1].hand["hand_cards"].cards[2].clone()];
+            hidden_cards.extend(game_state.players[0].hand["blind_cards"].cards.clone());
+            hidden_cards.extend(game_state.communal_cards["draw_pile"].cards.clone());
+            hidden_cards.sort_by_key(|card| card.rank.clone());
+            hidden_cards
+        };
+        let original_hidden_cards = hidden_cards(&game_state);
 
-            // let player = self.player_on_turn();
-            // if player.hand.cards.len() == 0 {
-            //     println!("{} wins!", player.name);
-            //     return;
-            // }
+        let mut rng = Pcg64::seed_from_u64(0);
+        let mut hidden_cards_moved = false;
+        for _ in 0..10 {
+            let mut shuffled_state = game_state.clone();
+            shuffled_state.shuffle_hidden_cards(0, &mut rng);
 
-            self.advance_player_turn();
+            assert_eq!(
+                shuffled_state.players[0].hand["hand_cards"].cards,
+                cards(&[5, 9])
+            );
+            assert_eq!(
+                shuffled_state.players[1].hand["hand_cards"].cards[..2],
+                cards(&[2, 3])[..]
+            );
+            assert_eq!(
+                shuffled_state.communal_cards["active_pile"].cards,
+                cards(&[4])
+            );
+            assert_eq!(hidden_cards(&shuffled_state), original_hidden_cards);
+            hidden_cards_moved |= shuffled_state.communal_cards["draw_pile"].cards
+                != game_state.communal_cards["draw_pile"].cards;
         }
+        assert!(hidden_cards_moved);
     }
 }
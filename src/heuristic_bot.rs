@@ -0,0 +1,344 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+use crate::card_deck::{Card, CardValue};
+use crate::game_error::GameError;
+use crate::game_state::GameState;
+use crate::player_controller::{ActionOption, CardChoice, PlayerController};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(difficulty: &str) -> Result<Self, Self::Err> {
+        match difficulty {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!(
+                "Unknown difficulty: {}. Use easy, medium or hard",
+                difficulty
+            )),
+        }
+    }
+}
+
+// How the heuristic bot scores the cards it could play or swap. The highest
+// scoring choice wins
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeuristicWeights {
+    // Per card played at once, so pairs and triples go down together
+    pub card_played: f64,
+    // Per point of value played, so the lowest cards go first
+    pub value_played: f64,
+    // Per wild card played, so they're kept until nothing else can be played
+    pub wild_played: f64,
+    // How strong a wild card counts as when swapping. Numeric cards count as
+    // their value
+    pub wild_strength: f64,
+    // Chance of making a random allowed choice instead of the best one
+    pub mistake_chance: f64,
+    // The card group the bot swaps its strongest cards into during setup.
+    // Poo Head players play their lower cards after their hand and before
+    // their blind cards
+    pub strong_card_group: String,
+}
+
+impl HeuristicWeights {
+    pub fn for_difficulty(difficulty: Difficulty) -> Self {
+        match difficulty {
+            // Doesn't think wild cards are worth saving, and slips up a lot
+            Difficulty::Easy => HeuristicWeights {
+                card_played: 3.0,
+                value_played: 1.0,
+                wild_played: 0.0,
+                wild_strength: 0.0,
+                mistake_chance: 0.25,
+                strong_card_group: "lower_cards".into(),
+            },
+            Difficulty::Medium => HeuristicWeights {
+                card_played: 3.0,
+                value_played: 1.0,
+                wild_played: 20.0,
+                wild_strength: 11.0,
+                mistake_chance: 0.1,
+                strong_card_group: "lower_cards".into(),
+            },
+            Difficulty::Hard => HeuristicWeights {
+                card_played: 3.0,
+                value_played: 1.0,
+                wild_played: 20.0,
+                wild_strength: 11.0,
+                mistake_chance: 0.0,
+                strong_card_group: "lower_cards".into(),
+            },
+        }
+    }
+}
+
+// A rule-aware bot for Poo Head. It never picks up the active pile while it
+// can play, plays its lowest cards first and saves wild cards for when
+// nothing else can be played. It only gets to see what its player can, so
+// blind cards are as much of a gamble for it as for anyone
+pub struct HeuristicController {
+    weights: HeuristicWeights,
+    rng: Pcg64,
+}
+
+impl HeuristicController {
+    pub fn new(weights: HeuristicWeights, seed: u64) -> Self {
+        HeuristicController {
+            weights,
+            rng: Pcg64::seed_from_u64(seed),
+        }
+    }
+
+    // The index of the highest score, or sometimes a random one as a mistake.
+    // Ties go to the first
+    fn pick(&mut self, scores: Vec<(usize, f64)>) -> Option<usize> {
+        if self.rng.gen_bool(self.weights.mistake_chance) {
+            return scores.choose(&mut self.rng).map(|(index, _)| *index);
+        }
+
+        let mut best: Option<(usize, f64)> = None;
+        for (index, score) in scores {
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((index, score));
+            }
+        }
+
+        return best.map(|(index, _)| index);
+    }
+
+    fn card_strength(&self, card: &Card, game_state: &GameState) -> Result<f64, GameError> {
+        match game_state.card_value(&card.rank)? {
+            CardValue::Wild => Ok(self.weights.wild_strength),
+            CardValue::Numeric(value) => Ok(*value as f64),
+        }
+    }
+
+    fn play_score(
+        &self,
+        card_indices: &[usize],
+        card_choice: &CardChoice,
+        game_state: &GameState,
+    ) -> Result<f64, GameError> {
+        let mut score = 0.0;
+        let mut hidden_card_count = 0;
+        for card_index in card_indices.iter() {
+            match &card_choice.visible_source_cards[*card_index] {
+                Some(card) => {
+                    score += self.weights.card_played;
+                    score -= match game_state.card_value(&card.rank)? {
+                        CardValue::Wild => self.weights.wild_played,
+                        CardValue::Numeric(value) => self.weights.value_played * *value as f64,
+                    };
+                }
+                // Hidden cards, eg. blind cards, are a gamble whichever is
+                // played. Every one after the first has to match it as well,
+                // which it rarely will, so they're played one at a time
+                None => {
+                    hidden_card_count += 1;
+                    if hidden_card_count == 1 {
+                        score += self.weights.card_played;
+                    } else {
+                        score -= self.weights.card_played;
+                    }
+                }
+            }
+        }
+
+        return Ok(score);
+    }
+
+    // Visible cards' indices, weakest first
+    fn weakest_first(
+        &self,
+        visible_cards: &[Option<Card>],
+        game_state: &GameState,
+    ) -> Result<Vec<(usize, f64)>, GameError> {
+        let mut strengths = vec![];
+        for (card_index, visible_card) in visible_cards.iter().enumerate() {
+            if let Some(card) = visible_card {
+                strengths.push((card_index, self.card_strength(card, game_state)?));
+            }
+        }
+
+        strengths.sort_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs));
+        return Ok(strengths);
+    }
+
+    // Moves the strongest cards into the strong card group, in return for its
+    // weakest. Other swaps give up as few cards as they're allowed to
+    fn swap_selection(
+        &self,
+        card_choice: &CardChoice,
+        game_state: &GameState,
+    ) -> Result<Vec<usize>, GameError> {
+        let mut source_strengths =
+            self.weakest_first(&card_choice.visible_source_cards, game_state)?;
+
+        if card_choice.destination == self.weights.strong_card_group
+            && card_choice.required_count.is_none()
+        {
+            source_strengths.reverse();
+            let destination_strengths =
+                self.weakest_first(&card_choice.visible_destination_cards, game_state)?;
+
+            return Ok(source_strengths
+                .iter()
+                .zip(destination_strengths.iter())
+                .take_while(|((_, source_strength), (_, destination_strength))| {
+                    source_strength > destination_strength
+                })
+                .map(|((card_index, _), _)| *card_index)
+                .collect());
+        }
+
+        let required_count = card_choice.required_count.unwrap_or(0);
+        return Ok(source_strengths
+            .iter()
+            .take(required_count)
+            .map(|(card_index, _)| *card_index)
+            .collect());
+    }
+}
+
+impl PlayerController for HeuristicController {
+    // Prefers whatever leaves its player holding the fewest cards, so it only
+    // picks up when it has to. Never undoes
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        _player_index: usize,
+        options: &[ActionOption],
+    ) -> Result<usize, GameError> {
+        let mut scores = vec![];
+        for (option_index, option) in options.iter().enumerate() {
+            match option {
+                ActionOption::Action(action) => scores.push((
+                    option_index,
+                    -(action.card_count_change(game_state)? as f64),
+                )),
+                ActionOption::EndTurn => scores.push((option_index, 0.0)),
+                ActionOption::Undo => {}
+            }
+        }
+
+        return self
+            .pick(scores)
+            .ok_or_else(|| GameError::Invariant("No options to choose from".into()));
+    }
+
+    fn choose_cards(
+        &mut self,
+        game_state: &GameState,
+        _player_index: usize,
+        card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError> {
+        if card_choice.is_swap() {
+            let swap_selection = self.swap_selection(card_choice, game_state)?;
+            if card_choice
+                .selection_problem(&swap_selection, game_state)?
+                .is_none()
+            {
+                return Ok(swap_selection);
+            }
+        }
+
+        let allowed_selections = card_choice.allowed_selections(game_state)?;
+        let mut scores = vec![];
+        for (selection_index, selection) in allowed_selections.iter().enumerate() {
+            scores.push((
+                selection_index,
+                self.play_score(selection, card_choice, game_state)?,
+            ));
+        }
+
+        return self
+            .pick(scores)
+            .map(|selection_index| allowed_selections[selection_index].clone())
+            .ok_or_else(|| {
+                GameError::Invariant(format!(
+                    "No cards can be moved from {} into {}",
+                    card_choice.source, card_choice.destination
+                ))
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_deck::{CardRank, Suit};
+    use crate::game_rules::poo_head_rules;
+    use crate::player::Player;
+
+    fn cards(ranks: &[usize]) -> Vec<Card> {
+        ranks
+            .iter()
+            .map(|rank| Card::new(Suit::Club, CardRank::from_usize(*rank)))
+            .collect()
+    }
+
+    #[test]
+    fn bot_plays_its_lowest_legal_card_and_saves_its_wild_ones() {
+        let players = vec![
+            Player::from_spec("Ann").unwrap(),
+            Player::from_spec("Bob").unwrap(),
+        ];
+        let mut game_state = GameState::new(poo_head_rules(), players, 0);
+        game_state.turn_count = 1;
+        game_state.start_turn().unwrap();
+        game_state
+            .communal_cards
+            .get_mut("active_pile")
+            .unwrap()
+            .cards = cards(&[6]);
+        // The two is wild and the four can't go on a six
+        game_state.players[0]
+            .hand
+            .get_mut("hand_cards")
+            .unwrap()
+            .cards = cards(&[2, 4, 9, 7]);
+
+        let action = game_state
+            .game_rules()
+            .available_actions(&game_state)
+            .unwrap()
+            .into_iter()
+            .find(|action| action.description == "Play cards")
+            .cloned()
+            .unwrap();
+        let mut controller =
+            HeuristicController::new(HeuristicWeights::for_difficulty(Difficulty::Hard), 0);
+        action.execute(&mut game_state, &mut controller).unwrap();
+
+        assert_eq!(
+            game_state.communal_cards["active_pile"].cards,
+            cards(&[6, 7])
+        );
+    }
+}
@@ -1,41 +1,428 @@
-use std::collections::HashMap;
+#![allow(clippy::needless_return)]
+
 use std::fs;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use clap::Parser;
+use serde_json::json;
+
+mod active_effects;
+
+mod cli;
+use cli::{Cli, Command, OutputFormat, PlayArgs, ResumeArgs, ServeArgs, SimulateArgs};
 
 mod player;
-use player::Player;
+use player::{ControllerKind, Player};
 
 mod card_deck;
 
+mod game_error;
+
+mod game_log;
+use game_log::{GameLog, LogEntry};
+
 mod game_rules;
 use game_rules::GameRules;
 
 mod game_state;
-use game_state::GameState;
+use game_state::{GameResult, GameState};
+
+mod heuristic_bot;
+
+mod mcts_bot;
+
+mod network;
+use network::{ReconnectSettings, RemoteTable};
+
+mod player_controller;
+use player_controller::{controllers_for, PlayerController};
+
+mod snapshot;
+use snapshot::GameSnapshot;
+
+mod table_view;
+
+mod random_bot;
+
+mod simulation;
+use simulation::SimulationSettings;
 
 mod user_input;
 
-fn main() {
-    let yams =
-        fs::read_to_string("poo_head_rules.yaml").expect("Something went wrong reading the file");
-    let game_rules: GameRules = serde_yaml::from_str(&yams).unwrap();
-
-    // TODO: Get players from user. Use min and max player count from game rules
-    let players: Vec<Player> = vec![
-        Player {
-            name: "Alice".into(),
-            hand: HashMap::new(),
-        },
-        Player {
-            name: "Bob".into(),
-            hand: HashMap::new(),
-        },
-    ];
-
-    let mut game_state = GameState::new(game_rules, players);
+fn exit_with_error(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
+
+fn load_game_rules(rules_path: &Path) -> Result<GameRules, String> {
+    let yams = fs::read_to_string(rules_path)
+        .map_err(|error| format!("Couldn't read {}: {}", rules_path.display(), error))?;
+
+    return serde_yaml::from_str(&yams)
+        .map_err(|error| format!("Couldn't parse {}: {}", rules_path.display(), error));
+}
+
+// Prints every problem with the rules and returns whether there were none
+fn validate(rules_path: &Path, game_rules: &GameRules, format: OutputFormat) -> bool {
+    let problems = game_rules.validate();
+    match format {
+        OutputFormat::Text => {
+            for problem in problems.iter() {
+                eprintln!("{}: {}", rules_path.display(), problem);
+            }
+        }
+        OutputFormat::Json if !problems.is_empty() => println!(
+            "{}",
+            json!({
+                "rules_path": rules_path,
+                "valid": false,
+                "problems": problems
+                    .iter()
+                    .map(|problem| json!({"path": problem.path, "error": problem.error.to_string()}))
+                    .collect::<Vec<_>>(),
+            })
+        ),
+        OutputFormat::Json => {}
+    }
+
+    return problems.is_empty();
+}
+
+fn load_valid_game_rules(rules_path: &Path, format: OutputFormat) -> GameRules {
+    let game_rules = load_game_rules(rules_path).unwrap_or_else(|error| exit_with_error(error));
+    if !validate(rules_path, &game_rules, format) {
+        process::exit(1);
+    }
+
+    return game_rules;
+}
+
+fn players_from_specs(player_specs: &[String]) -> Vec<Player> {
+    return player_specs
+        .iter()
+        .map(|player_spec| {
+            Player::from_spec(player_spec).unwrap_or_else(|error| exit_with_error(error))
+        })
+        .collect();
+}
+
+fn check_player_count(game_rules: &GameRules, player_count: usize) {
+    if !(game_rules.min_player_count..=game_rules.max_player_count).contains(&player_count) {
+        exit_with_error(format!(
+            "This game needs {} to {} players. Given: {}",
+            game_rules.min_player_count, game_rules.max_player_count, player_count,
+        ));
+    }
+}
+
+fn play(play_args: PlayArgs, format: OutputFormat) {
+    let game_rules = load_valid_game_rules(&play_args.rules_path, format);
+
+    let player_specs = if play_args.players.is_empty() {
+        user_input::read_player_names(game_rules.min_player_count, game_rules.max_player_count)
+            .unwrap_or_else(|error| exit_with_error(error))
+    } else {
+        play_args.players
+    };
+    check_player_count(&game_rules, player_specs.len());
+
+    let seed = play_args.new_game.seed.unwrap_or_else(rand::random);
+    let mut game_state = GameState::new(game_rules, players_from_specs(&player_specs), seed);
+    game_state.deal();
+    game_state.autosave_path = play_args.save;
+    game_state.log_path = play_args.new_game.log;
+    game_state.undo_allowed = play_args.house_rules.undo;
+
+    // Printed so a game can be played again with the same deal, eg. for a
+    // bug report
+    println!("Seed: {}", game_state.seed);
+
+    let controllers = controllers_for(&game_state.players, seed, play_args.terminal.hot_seat);
+    play_to_end(game_state, controllers, format);
+}
+
+// Picks a saved game back up. It keeps saving to the snapshot it came from
+// unless told otherwise
+fn resume(resume_args: ResumeArgs, format: OutputFormat) {
+    let game_rules = load_valid_game_rules(&resume_args.rules_path, format);
+
+    let mut game_state = GameSnapshot::load(&resume_args.snapshot_path)
+        .and_then(|game_snapshot| game_snapshot.into_game_state(&game_rules))
+        .unwrap_or_else(|error| {
+            exit_with_error(format!(
+                "{}: {}",
+                resume_args.snapshot_path.display(),
+                error
+            ))
+        });
+    game_state.autosave_path = Some(resume_args.save.unwrap_or(resume_args.snapshot_path));
+    game_state.undo_allowed |= resume_args.house_rules.undo;
+
+    println!("Seed: {}", game_state.seed);
+
+    let controllers = controllers_for(
+        &game_state.players,
+        game_state.seed,
+        resume_args.terminal.hot_seat,
+    );
+    play_to_end(game_state, controllers, format);
+}
+
+// Plays a logged game again from the start with the logged seed and choices,
+// then checks it ended up in the same state as the original
+fn replay(log_path: &Path, rules_path: &Path, format: OutputFormat) {
+    let game_rules = load_valid_game_rules(rules_path, format);
+    let game_log = GameLog::load(log_path).unwrap_or_else(|error| exit_with_error(error));
+
+    if game_log.rules_fingerprint != game_rules.fingerprint() {
+        exit_with_error(format!(
+            "{}: The game was played with different rules. Logged rules fingerprint: {}. Current rules fingerprint: {}",
+            log_path.display(),
+            game_log.rules_fingerprint,
+            game_rules.fingerprint(),
+        ));
+    }
+
+    let game_state = game_log
+        .replay(game_rules)
+        .unwrap_or_else(|error| exit_with_error(error));
+
+    let logged_actions = game_log
+        .entries
+        .iter()
+        .filter(|log_entry| matches!(log_entry, LogEntry::ActionExecuted { .. }))
+        .collect::<Vec<&LogEntry>>();
+    let replayed_actions = game_state
+        .game_log
+        .entries
+        .iter()
+        .filter(|log_entry| matches!(log_entry, LogEntry::ActionExecuted { .. }))
+        .collect::<Vec<&LogEntry>>();
+
+    // A game killed part way through may have been in the middle of an
+    // action, so the replay only has to get at least as far as its log
+    let same_actions = match game_log.state_fingerprint {
+        Some(_) => logged_actions == replayed_actions,
+        None => replayed_actions.starts_with(&logged_actions),
+    };
+    if !same_actions {
+        exit_with_error(format!(
+            "{}: The replay didn't run the same actions as the log",
+            log_path.display()
+        ));
+    }
+
+    let state_fingerprint = game_state.fingerprint();
+    if let Some(logged_state_fingerprint) = &game_log.state_fingerprint {
+        if state_fingerprint != *logged_state_fingerprint {
+            exit_with_error(format!(
+                "{}: The replay ended in a different state. Logged state fingerprint: {}. Replayed state fingerprint: {}",
+                log_path.display(),
+                logged_state_fingerprint,
+                state_fingerprint,
+            ));
+        }
+    }
+
+    let finished = game_log.state_fingerprint.is_some();
+    match format {
+        OutputFormat::Text => {
+            println!();
+            if finished {
+                println!("The replay matches {}", log_path.display());
+            } else {
+                println!(
+                    "The replay matches {} as far as it goes. The game was stopped before its log was finished",
+                    log_path.display()
+                );
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            json!({"log_path": log_path, "matches": true, "finished": finished, "state_fingerprint": state_fingerprint})
+        ),
+    }
+}
+
+// Plays bots against each other without printing anything until the report
+fn simulate(simulate_args: SimulateArgs, format: OutputFormat) {
+    let game_rules = load_valid_game_rules(&simulate_args.rules_path, format);
+
+    let players = players_from_specs(&simulate_args.players);
+    check_player_count(&game_rules, players.len());
+    if let Some(player) = players
+        .iter()
+        .find(|player| player.controller == ControllerKind::Human)
+    {
+        exit_with_error(format!(
+            "Every seat in a simulation needs a bot. Given a person: {}",
+            player.name
+        ));
+    }
+
+    let simulation_settings = SimulationSettings {
+        game_count: simulate_args.games,
+        first_seed: simulate_args.seed.unwrap_or_else(rand::random),
+        thread_count: simulate_args.threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |thread_count| thread_count.get())
+        }),
+        turn_limit: simulate_args.turn_limit,
+        decision_limit: simulate_args.decision_limit,
+    };
+
+    let simulation_report = simulation::simulate(&game_rules, &players, &simulation_settings)
+        .unwrap_or_else(|error| exit_with_error(error));
+    match format {
+        OutputFormat::Text => println!("{}", simulation_report),
+        OutputFormat::Json => println!("{}", json!(simulation_report)),
+    }
+}
+
+// Hosts a game on the network. The server holds the game and plays the bots,
+// and people play their seats with connect
+fn serve(serve_args: ServeArgs, format: OutputFormat) {
+    let game_rules = load_valid_game_rules(&serve_args.rules_path, format);
+
+    let players = players_from_specs(&serve_args.players);
+    check_player_count(&game_rules, players.len());
+
+    let fallback_bot =
+        serve_args.fallback_bot.map(
+            |fallback_bot| match fallback_bot.parse::<ControllerKind>() {
+                Ok(ControllerKind::Human) => exit_with_error("The fallback bot has to be a bot"),
+                Ok(controller_kind) => controller_kind,
+                Err(error) => exit_with_error(error),
+            },
+        );
+    let reconnect_settings = ReconnectSettings {
+        grace_period: Duration::from_secs(serve_args.grace_period),
+        decision_timeout: Duration::from_secs(serve_args.decision_timeout),
+        fallback_bot,
+    };
+
+    let seed = serve_args.new_game.seed.unwrap_or_else(rand::random);
+    let mut game_state = GameState::new(game_rules, players, seed);
     game_state.deal();
+    game_state.log_path = serve_args.new_game.log;
+    game_state.undo_allowed = serve_args.house_rules.undo;
+
+    let bind = serve_args.bind;
+    let listener = TcpListener::bind(&bind)
+        .unwrap_or_else(|error| exit_with_error(format!("Couldn't listen on {}: {}", bind, error)));
+    match listener.local_addr() {
+        Ok(address) => println!("Listening on {}", address),
+        Err(error) => exit_with_error(error),
+    }
+    println!("Seed: {}", game_state.seed);
+
+    let remote_table = RemoteTable::seat_players(listener, &game_state.players, reconnect_settings)
+        .unwrap_or_else(|error| exit_with_error(error));
+    let mut controllers = network::controllers_for(&game_state.players, seed, &remote_table);
+
+    let result = game_state.play_game(&mut controllers);
+    remote_table.borrow_mut().game_over(&game_state, &result);
+    match result {
+        Ok(game_result) => print_game_result(&game_state, &game_result, format),
+        Err(error) => exit_with_error(error),
+    }
+}
+
+// Plays a seat in a game hosted with serve
+fn connect(
+    address: &str,
+    player_name: Option<String>,
+    seat_token: Option<String>,
+    format: OutputFormat,
+) {
+    let (finishing_order, turn_count) = network::connect(address, player_name, seat_token)
+        .unwrap_or_else(|error| exit_with_error(error));
+
+    match format {
+        OutputFormat::Text => print_finishing_order(&finishing_order, turn_count),
+        OutputFormat::Json => println!(
+            "{}",
+            json!({"turn_count": turn_count, "finishing_order": finishing_order})
+        ),
+    }
+}
+
+fn print_finishing_order(finishing_order: &[impl std::fmt::Display], turn_count: usize) {
+    println!();
+    println!("Game over after {} turns", turn_count);
+    for (place, player_name) in finishing_order.iter().enumerate() {
+        println!("{}. {}", place + 1, player_name);
+    }
+}
+
+fn print_game_result(game_state: &GameState, game_result: &GameResult, format: OutputFormat) {
+    let finishing_order = game_result
+        .finishing_order
+        .iter()
+        .map(|player_index| game_state.players[*player_index].name.as_str())
+        .collect::<Vec<&str>>();
+
+    match format {
+        OutputFormat::Text => print_finishing_order(&finishing_order, game_result.turn_count),
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "seed": game_state.seed,
+                "turn_count": game_result.turn_count,
+                "finishing_order": finishing_order,
+            })
+        ),
+    }
+}
+
+fn play_to_end(
+    mut game_state: GameState,
+    mut controllers: Vec<Box<dyn PlayerController>>,
+    format: OutputFormat,
+) {
+    match game_state.play_game(&mut controllers) {
+        Ok(game_result) => print_game_result(&game_state, &game_result, format),
+        Err(error) => exit_with_error(error),
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Play(play_args)) => play(play_args, cli.format),
+        Some(Command::Validate { rules_path }) => {
+            let game_rules =
+                load_game_rules(&rules_path).unwrap_or_else(|error| exit_with_error(error));
+
+            if !validate(&rules_path, &game_rules, cli.format) {
+                process::exit(1);
+            }
 
-    // TODO: Handle user errors
-    game_state.play_game();
+            match cli.format {
+                OutputFormat::Text => println!("{} is valid", rules_path.display()),
+                OutputFormat::Json => println!(
+                    "{}",
+                    json!({"rules_path": rules_path, "valid": true, "problems": []})
+                ),
+            }
+        }
+        Some(Command::Resume(resume_args)) => resume(resume_args, cli.format),
+        Some(Command::Replay {
+            log_path,
+            rules_path,
+        }) => replay(&log_path, &rules_path, cli.format),
+        Some(Command::Simulate(simulate_args)) => simulate(simulate_args, cli.format),
+        Some(Command::Serve(serve_args)) => serve(serve_args, cli.format),
+        Some(Command::Connect {
+            address,
+            name,
+            seat_token,
+        }) => connect(&address, name, seat_token, cli.format),
+        None => play(Default::default(), cli.format),
+    }
 }
 
 // fn play_turn(game_state: &mut GameState) {
@@ -0,0 +1,593 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+use crate::game_error::{GameError, InputError};
+use crate::game_log::LogEntry;
+use crate::game_state::{GameResult, GameState};
+use crate::player_controller::{ActionOption, CardChoice, PlayerController};
+
+// How long the bot thinks about each decision
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SearchBudget {
+    // Playouts per decision. Seeded games play out the same way every time
+    Iterations(usize),
+    Milliseconds(u64),
+}
+
+impl Default for SearchBudget {
+    fn default() -> Self {
+        SearchBudget::Iterations(100)
+    }
+}
+
+impl fmt::Display for SearchBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchBudget::Iterations(iterations) => write!(f, "{}", iterations),
+            SearchBudget::Milliseconds(milliseconds) => write!(f, "{}ms", milliseconds),
+        }
+    }
+}
+
+// Either a number of playouts, eg. "500", or a time, eg. "250ms"
+impl FromStr for SearchBudget {
+    type Err = String;
+
+    fn from_str(search_budget: &str) -> Result<Self, Self::Err> {
+        let invalid_budget = || {
+            format!(
+                "Invalid search budget: {}. Use a number of playouts, eg. 500, or milliseconds, eg. 250ms",
+                search_budget
+            )
+        };
+
+        let budget = match search_budget.strip_suffix("ms") {
+            Some(milliseconds) => {
+                SearchBudget::Milliseconds(milliseconds.parse().map_err(|_| invalid_budget())?)
+            }
+            None => SearchBudget::Iterations(search_budget.parse().map_err(|_| invalid_budget())?),
+        };
+
+        match budget {
+            SearchBudget::Iterations(0) | SearchBudget::Milliseconds(0) => Err(invalid_budget()),
+            _ => Ok(budget),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsSettings {
+    pub budget: SearchBudget,
+    // How much the search favours trying less explored choices over the ones
+    // that have done well so far
+    pub exploration: f64,
+    // Playouts that go on for more decisions than this are stopped and scored
+    // by how many cards each player holds. Short playouts score the choice
+    // being searched rather than the luck of a long game, and stalled games
+    // can't hang the search
+    pub playout_decision_limit: usize,
+}
+
+impl MctsSettings {
+    pub fn new(budget: SearchBudget) -> Self {
+        MctsSettings {
+            budget,
+            exploration: std::f64::consts::SQRT_2,
+            playout_decision_limit: 40,
+        }
+    }
+}
+
+// A choice in terms the search can compare across playouts
+#[derive(Clone, Debug, PartialEq)]
+enum ChoiceKey {
+    Action(usize),
+    Cards(Vec<usize>),
+}
+
+// Choices made from a node are scored for whoever made them
+#[derive(Default)]
+struct Node {
+    children: Vec<(ChoiceKey, usize)>,
+    visits: usize,
+    // How many times the choice could have been made, which differs between
+    // playouts when it depends on hidden cards
+    availability: usize,
+    total_reward: f64,
+}
+
+// The tree of decisions from the bot's current decision on, shared across
+// all the guesses at the hidden cards
+struct SearchTree {
+    nodes: Vec<Node>,
+}
+
+const ROOT: usize = 0;
+
+impl SearchTree {
+    fn new() -> Self {
+        SearchTree {
+            nodes: vec![Node::default()],
+        }
+    }
+
+    fn child(&self, node: usize, choice_key: &ChoiceKey) -> Option<usize> {
+        self.nodes[node]
+            .children
+            .iter()
+            .find(|(child_key, _)| child_key == choice_key)
+            .map(|(_, child)| *child)
+    }
+
+    fn add_child(&mut self, node: usize, choice_key: ChoiceKey) -> usize {
+        let child = self.nodes.len();
+        self.nodes.push(Node::default());
+        self.nodes[node].children.push((choice_key, child));
+        return child;
+    }
+}
+
+// One game played out from the bot's decision. It follows the tree while
+// every choice there has been tried and adds one new choice. After that it
+// plays quickly, taking whichever action leaves the player holding the fewest
+// cards and picking cards at random
+struct Playout {
+    tree: SearchTree,
+    // Choices already made in the action the bot is part way through
+    script: VecDeque<LogEntry>,
+    // Who makes the decision being searched, and whether it's an action
+    root_player_index: usize,
+    root_is_action: bool,
+    // Set when the guessed cards can't be right because the game didn't go
+    // the way it really did, eg. a blind card turned out to be unplayable.
+    // The playout is thrown away
+    diverged: bool,
+    // None once the playout has left the tree
+    node: Option<usize>,
+    path: Vec<(usize, usize)>,
+    decision_count: usize,
+    exploration: f64,
+    decision_limit: usize,
+    rng: Pcg64,
+}
+
+impl Playout {
+    // Returns the index of the picked choice key. Out of the tree it's one of
+    // the choices with the lowest cost
+    fn decide(
+        &mut self,
+        player_index: usize,
+        choice_keys: &[ChoiceKey],
+        costs: &[isize],
+    ) -> Result<usize, GameError> {
+        let root_is_action = matches!(choice_keys.first(), Some(ChoiceKey::Action(_)));
+        if self.node == Some(ROOT)
+            && (player_index != self.root_player_index || root_is_action != self.root_is_action)
+        {
+            return Err(self.diverge());
+        }
+
+        self.count_decision()?;
+        if choice_keys.is_empty() {
+            return Err(InputError::EndOfInput.into());
+        }
+
+        let node = match self.node {
+            Some(node) => node,
+            None => {
+                let lowest_cost = costs.iter().min().copied().unwrap_or(0);
+                let cheapest_choices = (0..choice_keys.len())
+                    .filter(|choice_index| {
+                        costs.get(*choice_index).copied().unwrap_or(0) == lowest_cost
+                    })
+                    .collect::<Vec<usize>>();
+                return Ok(*cheapest_choices
+                    .choose(&mut self.rng)
+                    .expect("There's always a cheapest choice"));
+            }
+        };
+
+        let mut untried_choices = vec![];
+        let mut tried_choices = vec![];
+        for (choice_index, choice_key) in choice_keys.iter().enumerate() {
+            match self.tree.child(node, choice_key) {
+                Some(child) => {
+                    self.tree.nodes[child].availability += 1;
+                    tried_choices.push((choice_index, child));
+                }
+                None => untried_choices.push(choice_index),
+            }
+        }
+
+        if let Some(choice_index) = untried_choices.choose(&mut self.rng).copied() {
+            let child = self.tree.add_child(node, choice_keys[choice_index].clone());
+            self.tree.nodes[child].availability += 1;
+            self.path.push((child, player_index));
+            self.node = None;
+            return Ok(choice_index);
+        }
+
+        // UCB1, counting how often each choice was available rather than how
+        // often its parent was visited
+        let mut best: Option<(usize, usize, f64)> = None;
+        for (choice_index, child) in tried_choices {
+            let child_node = &self.tree.nodes[child];
+            let visits = child_node.visits.max(1) as f64;
+            let score = child_node.total_reward / visits
+                + self.exploration * ((child_node.availability as f64).ln() / visits).sqrt();
+            if best.is_none_or(|(_, _, best_score)| score > best_score) {
+                best = Some((choice_index, child, score));
+            }
+        }
+
+        let (choice_index, child, _) = best.expect("Every choice is either tried or untried");
+        self.path.push((child, player_index));
+        self.node = Some(child);
+        return Ok(choice_index);
+    }
+
+    // Ends playouts that go on too long
+    fn count_decision(&mut self) -> Result<(), GameError> {
+        self.decision_count += 1;
+        if self.decision_count > self.decision_limit {
+            return Err(InputError::EndOfInput.into());
+        }
+
+        Ok(())
+    }
+
+    // Ends the playout
+    fn diverge(&mut self) -> GameError {
+        self.diverged = true;
+        return InputError::EndOfInput.into();
+    }
+
+    fn backpropagate(&mut self, rewards: &[f64]) {
+        self.tree.nodes[ROOT].visits += 1;
+        for (node, player_index) in self.path.iter() {
+            self.tree.nodes[*node].visits += 1;
+            self.tree.nodes[*node].total_reward += rewards[*player_index];
+        }
+    }
+}
+
+// Plays a seat during a playout, sharing the playout with every other seat
+struct PlayoutSeat {
+    playout: Rc<RefCell<Playout>>,
+}
+
+impl PlayerController for PlayoutSeat {
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        options: &[ActionOption],
+    ) -> Result<usize, GameError> {
+        let mut playout = self.playout.borrow_mut();
+        if playout.diverged {
+            return Err(InputError::EndOfInput.into());
+        }
+
+        if let Some(log_entry) = playout.script.pop_front() {
+            if let LogEntry::ActionChosen { action_index, .. } = log_entry {
+                return Ok(action_index);
+            }
+
+            return Err(playout.diverge());
+        }
+
+        let mut choice_keys = vec![];
+        let mut costs = vec![];
+        for (option_index, option) in options.iter().enumerate() {
+            choice_keys.push(ChoiceKey::Action(option_index));
+            costs.push(match option {
+                ActionOption::Action(action) => action.card_count_change(game_state)?,
+                ActionOption::EndTurn | ActionOption::Undo => 0,
+            });
+        }
+
+        return playout.decide(player_index, &choice_keys, &costs);
+    }
+
+    fn choose_cards(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError> {
+        let mut playout = self.playout.borrow_mut();
+        if playout.diverged {
+            return Err(InputError::EndOfInput.into());
+        }
+
+        if let Some(log_entry) = playout.script.pop_front() {
+            if let LogEntry::CardsChosen { card_indices, .. } = log_entry {
+                return Ok(card_indices);
+            }
+
+            return Err(playout.diverge());
+        }
+
+        if playout.node.is_none() {
+            playout.count_decision()?;
+            if let Some(selection) = card_choice.random_selection(game_state, &mut playout.rng)? {
+                return Ok(selection);
+            }
+        }
+
+        let allowed_selections = card_choice.allowed_selections(game_state)?;
+        let choice_keys = allowed_selections
+            .into_iter()
+            .map(ChoiceKey::Cards)
+            .collect::<Vec<ChoiceKey>>();
+        let choice_index = playout.decide(player_index, &choice_keys, &[])?;
+        match &choice_keys[choice_index] {
+            ChoiceKey::Cards(card_indices) => Ok(card_indices.clone()),
+            ChoiceKey::Action(_) => unreachable!("Card choices only have card keys"),
+        }
+    }
+
+    // Playouts only make choices the game allows, so a turned down choice
+    // means a scripted one didn't work with the guessed cards
+    fn show_message(&mut self, _game_state: &GameState, _player_index: usize, _message: &str) {
+        self.playout.borrow_mut().diverge();
+    }
+}
+
+// Scores each player from 1 for going out first to 0 for last. In unfinished
+// playouts the players still in share out the places left by how few cards
+// they hold, so holding fewer cards always scores better
+fn playout_rewards(game_state: &GameState, game_result: Option<GameResult>) -> Vec<f64> {
+    let finishing_order = match game_result {
+        Some(game_result) => game_result.finishing_order,
+        None => game_state.finishing_order.clone(),
+    };
+
+    let mut rewards = vec![0.0; game_state.players.len()];
+    let last_place = (rewards.len() - 1).max(1) as f64;
+    for (place, player_index) in finishing_order.iter().enumerate() {
+        rewards[*player_index] = 1.0 - place as f64 / last_place;
+    }
+
+    let players_still_in = (0..game_state.players.len())
+        .filter(|player_index| !finishing_order.contains(player_index))
+        .collect::<Vec<usize>>();
+    let best_place_left = 1.0 - finishing_order.len() as f64 / last_place;
+    let cards_still_held = players_still_in
+        .iter()
+        .map(|player_index| game_state.players[*player_index].card_count())
+        .sum::<usize>()
+        .max(1) as f64;
+    for player_index in players_still_in {
+        let card_share = game_state.players[player_index].card_count() as f64 / cards_still_held;
+        rewards[player_index] = best_place_left * (1.0 - card_share);
+    }
+
+    return rewards;
+}
+
+// Monte Carlo tree search that works for any rules. Each playout guesses the
+// cards its player can't see, then plays the game out from the start of the
+// current action through the rules' own available actions. It picks the
+// choice that was explored the most, which is the most robust one
+pub struct MctsController {
+    settings: MctsSettings,
+    rng: Pcg64,
+}
+
+impl MctsController {
+    pub fn new(settings: MctsSettings, seed: u64) -> Self {
+        MctsController {
+            settings,
+            rng: Pcg64::seed_from_u64(seed),
+        }
+    }
+
+    // Returns the index of the most visited choice out of the ones possible
+    // in the real game
+    fn search(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        choice_keys: &[ChoiceKey],
+    ) -> Result<usize, GameError> {
+        if choice_keys.len() < 2 {
+            return Ok(0);
+        }
+
+        let (action_start, script) = match game_state.action_start() {
+            Some(action_start) => action_start,
+            None => return Ok(0),
+        };
+
+        let started_at = Instant::now();
+        let mut tree = SearchTree::new();
+        let mut iterations = 0;
+        loop {
+            let out_of_budget = match self.settings.budget {
+                SearchBudget::Iterations(max_iterations) => iterations >= max_iterations,
+                SearchBudget::Milliseconds(milliseconds) => {
+                    started_at.elapsed() >= Duration::from_millis(milliseconds)
+                }
+            };
+            if out_of_budget {
+                break;
+            }
+            iterations += 1;
+
+            let mut playout_state = action_start.clone();
+            playout_state.autosave_path = None;
+            playout_state.log_path = None;
+            playout_state.undo_allowed = false;
+            // Playouts have their own decision limit, and stopping one
+            // shouldn't stop the real game
+            playout_state.decision_limit = None;
+            playout_state.turn_limit = None;
+            playout_state.shuffle_hidden_cards(player_index, &mut self.rng);
+
+            let playout = Rc::new(RefCell::new(Playout {
+                tree,
+                script: script.iter().cloned().collect(),
+                root_player_index: player_index,
+                root_is_action: matches!(choice_keys.first(), Some(ChoiceKey::Action(_))),
+                diverged: false,
+                node: Some(ROOT),
+                path: vec![],
+                decision_count: 0,
+                exploration: self.settings.exploration,
+                decision_limit: self.settings.playout_decision_limit,
+                rng: Pcg64::seed_from_u64(self.rng.gen()),
+            }));
+            let mut controllers = (0..playout_state.players.len())
+                .map(|_| {
+                    Box::new(PlayoutSeat {
+                        playout: Rc::clone(&playout),
+                    }) as Box<dyn PlayerController>
+                })
+                .collect::<Vec<_>>();
+
+            let game_result = match playout_state.play_game(&mut controllers) {
+                Ok(game_result) => Some(game_result),
+                Err(GameError::Input(InputError::EndOfInput)) => None,
+                Err(error) => return Err(error),
+            };
+
+            let mut playout = playout.borrow_mut();
+            if !playout.diverged {
+                playout.backpropagate(&playout_rewards(&playout_state, game_result));
+            }
+            tree = mem::replace(&mut playout.tree, SearchTree::new());
+        }
+
+        let mut most_visited: Option<(usize, usize)> = None;
+        for (choice_index, choice_key) in choice_keys.iter().enumerate() {
+            let visits = tree
+                .child(ROOT, choice_key)
+                .map_or(0, |child| tree.nodes[child].visits);
+            if most_visited.is_none_or(|(_, most_visits)| visits > most_visits) {
+                most_visited = Some((choice_index, visits));
+            }
+        }
+
+        return Ok(most_visited.map_or(0, |(choice_index, _)| choice_index));
+    }
+}
+
+impl PlayerController for MctsController {
+    // Never undoes. Once the turn is over there's nothing left to search
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        options: &[ActionOption],
+    ) -> Result<usize, GameError> {
+        if let Some(end_turn_index) = options
+            .iter()
+            .position(|option| *option == ActionOption::EndTurn)
+        {
+            return Ok(end_turn_index);
+        }
+
+        let action_count = options
+            .iter()
+            .filter(|option| matches!(option, ActionOption::Action(_)))
+            .count();
+        let choice_keys = (0..action_count)
+            .map(ChoiceKey::Action)
+            .collect::<Vec<ChoiceKey>>();
+
+        return self.search(game_state, player_index, &choice_keys);
+    }
+
+    fn choose_cards(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError> {
+        let allowed_selections = card_choice.allowed_selections(game_state)?;
+        if allowed_selections.is_empty() {
+            return Err(GameError::Invariant(format!(
+                "No cards can be moved from {} into {}",
+                card_choice.source, card_choice.destination
+            )));
+        }
+
+        let choice_keys = allowed_selections
+            .into_iter()
+            .map(ChoiceKey::Cards)
+            .collect::<Vec<ChoiceKey>>();
+        let choice_index = self.search(game_state, player_index, &choice_keys)?;
+        match &choice_keys[choice_index] {
+            ChoiceKey::Cards(card_indices) => Ok(card_indices.clone()),
+            ChoiceKey::Action(_) => unreachable!("Card choices only have card keys"),
+        }
+    }
+
+    fn wants_action_start(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_rules::poo_head_rules;
+    use crate::player::Player;
+
+    // A dealt three player game where each player has given up the given
+    // number of hand cards
+    fn dealt_game(cards_given_up: [usize; 3]) -> GameState {
+        let players = ["P0", "P1", "P2"]
+            .iter()
+            .map(|player_spec| Player::from_spec(player_spec).unwrap())
+            .collect();
+        let mut game_state = GameState::new(poo_head_rules(), players, 0);
+        game_state.deal();
+
+        for (player, card_count) in game_state.players.iter_mut().zip(cards_given_up) {
+            let hand_cards = &mut player.hand.get_mut("hand_cards").unwrap().cards;
+            hand_cards.truncate(hand_cards.len() - card_count);
+        }
+
+        return game_state;
+    }
+
+    #[test]
+    fn finished_playouts_score_by_place() {
+        let game_state = dealt_game([0, 0, 0]);
+        let game_result = GameResult {
+            finishing_order: vec![2, 0, 1],
+            turn_count: 0,
+        };
+
+        assert_eq!(
+            playout_rewards(&game_state, Some(game_result)),
+            vec![0.5, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn unfinished_playouts_score_fewer_cards_higher() {
+        let mut game_state = dealt_game([3, 1, 0]);
+        let rewards = playout_rewards(&game_state, None);
+        assert!(rewards[0] > rewards[1] && rewards[1] > rewards[2]);
+        assert!(rewards.iter().all(|reward| (0.0..=1.0).contains(reward)));
+
+        // Players still in always score below anyone who's gone out
+        game_state.finishing_order = vec![1];
+        let rewards = playout_rewards(&game_state, None);
+        assert_eq!(rewards[1], 1.0);
+        assert!(rewards[0] > rewards[2] && rewards[0] < 0.5);
+    }
+}
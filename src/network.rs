@@ -0,0 +1,1122 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::card_deck::Card;
+use crate::game_error::GameError;
+use crate::game_log::LogEntry;
+use crate::game_state::{GameResult, GameState};
+use crate::player::{ControllerKind, Player};
+use crate::player_controller::{bot_for, ActionOption, CardChoice, PlayerController};
+use crate::table_view::TableView;
+use crate::user_input;
+
+// Clients that connect but don't say which seat they want are dropped after
+// this long
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Connections that can't take a message in this long are treated as lost
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How often the server stops waiting for an answer to seat anyone who has
+// joined since
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Everything the server sends. Each message is one line of JSON
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    // The client has taken a seat, or taken it back. The seat token gets the
+    // seat back after losing the connection
+    Welcome {
+        player_index: usize,
+        player_name: String,
+        seat_token: String,
+    },
+    // Every action that ran since the client was last caught up, including
+    // consequences
+    ActionExecuted {
+        player_name: String,
+        description: String,
+    },
+    // The table as the client's player sees it, whenever it changes
+    Table {
+        table_view: TableView,
+    },
+    // Answered with ClientMessage::ChooseAction
+    ChooseAction {
+        options: Vec<String>,
+    },
+    // Answered with ClientMessage::ChooseCards. The cards are the source cards
+    // as the client's player sees them
+    ChooseCards {
+        source: String,
+        destination: String,
+        cards: Vec<Option<Card>>,
+        required_count: Option<usize>,
+    },
+    // Eg. why a choice was turned down
+    Message {
+        text: String,
+    },
+    GameOver {
+        finishing_order: Vec<String>,
+        turn_count: usize,
+    },
+}
+
+// Everything a client sends. Each message is one line of JSON
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    // Takes back the seat the token was given for. Without a token it takes
+    // the free seat for the player with this name, or the first free seat if
+    // no name is given
+    Join {
+        player_name: Option<String>,
+        #[serde(default)]
+        seat_token: Option<String>,
+    },
+    // An index into the options
+    ChooseAction {
+        option_index: usize,
+    },
+    // Indices into the source cards
+    ChooseCards {
+        card_indices: Vec<usize>,
+    },
+}
+
+// One end of a connection between the server and a client
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    // The start of a message whose end hasn't arrived yet
+    pending: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        // Messages are small and usually waited on, so they go straight out
+        // rather than being held back to fill a packet
+        stream.set_nodelay(true)?;
+        return Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            pending: vec![],
+        });
+    }
+
+    fn send(&mut self, message: &impl Serialize) -> io::Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        return self.writer.flush();
+    }
+
+    // The next message, or a description of the line if it isn't one. A
+    // closed connection is an UnexpectedEof error, and running out of the
+    // socket's read timeout is a TimedOut error
+    fn receive<T: DeserializeOwned>(&mut self) -> io::Result<Result<T, String>> {
+        return self
+            .try_receive()?
+            .ok_or_else(|| io::ErrorKind::TimedOut.into());
+    }
+
+    // Like receive, but None if the read timed out before a whole message
+    // arrived. The part that did arrive is kept for the next call
+    fn try_receive<T: DeserializeOwned>(&mut self) -> io::Result<Option<Result<T, String>>> {
+        match self.reader.read_until(b'\n', &mut self.pending) {
+            Ok(_) if self.pending.last() == Some(&b'\n') => {}
+            Ok(_) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(None)
+            }
+            Err(error) => return Err(error),
+        }
+
+        let line = String::from_utf8_lossy(&mem::take(&mut self.pending)).into_owned();
+        return Ok(Some(serde_json::from_str(&line).map_err(|error| {
+            format!("Couldn't read message {}: {}", line.trim(), error)
+        })));
+    }
+}
+
+// A client that has connected and said which seat it wants
+struct Join {
+    connection: Connection,
+    address: SocketAddr,
+    player_name: Option<String>,
+    seat_token: Option<String>,
+}
+
+// Reads the join message off a new connection
+fn read_join(stream: TcpStream, address: SocketAddr) -> Result<Join, String> {
+    stream
+        .set_read_timeout(Some(JOIN_TIMEOUT))
+        .map_err(|error| error.to_string())?;
+    let mut connection = Connection::new(stream).map_err(|error| error.to_string())?;
+
+    let (player_name, seat_token) = match connection.receive().map_err(|error| error.to_string())? {
+        Ok(ClientMessage::Join {
+            player_name,
+            seat_token,
+        }) => (player_name, seat_token),
+        Ok(client_message) => {
+            return Err(format!(
+                "Expected to join first. Given: {:?}",
+                client_message
+            ))
+        }
+        Err(error) => return Err(error),
+    };
+
+    // Both ends of the connection share the socket's settings. From now on
+    // reads only wait a moment, so the server can see to other players
+    // while it waits for an answer
+    connection
+        .writer
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(|error| error.to_string())?;
+    connection
+        .writer
+        .set_write_timeout(Some(SEND_TIMEOUT))
+        .map_err(|error| error.to_string())?;
+
+    return Ok(Join {
+        connection,
+        address,
+        player_name,
+        seat_token,
+    });
+}
+
+// Accepts connections for as long as the server runs, so players can join and
+// rejoin at any point in the game. Each join is read off its own thread so a
+// slow client can't hold the game or anyone else's join up
+fn listen_for_joins(listener: TcpListener) -> Receiver<Join> {
+    let (join_sender, join_receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    eprintln!("Couldn't accept a connection: {}", error);
+                    continue;
+                }
+            };
+            let address = match stream.peer_addr() {
+                Ok(address) => address,
+                Err(_) => continue,
+            };
+
+            let join_sender = join_sender.clone();
+            thread::spawn(move || match read_join(stream, address) {
+                // Nobody is listening once the game is over
+                Ok(join) => {
+                    let _ = join_sender.send(join);
+                }
+                Err(error) => eprintln!("{} couldn't join: {}", address, error),
+            });
+        }
+    });
+
+    return join_receiver;
+}
+
+// A seat that a person plays from another computer
+struct RemoteSeat {
+    player_name: String,
+    // None until someone joins, after which only this token gets the seat
+    seat_token: Option<String>,
+    // None while the player is disconnected
+    connection: Option<Connection>,
+    // Whether the fallback bot is playing until the player comes back
+    bot_playing: bool,
+    // How much of the game log and which table this seat has been sent
+    sent_log_length: usize,
+    sent_table_view: Option<TableView>,
+}
+
+impl RemoteSeat {
+    // Sends what happened since the seat was last caught up, and the table if
+    // it changed
+    fn catch_up(&mut self, game_state: &GameState, player_index: usize) -> io::Result<()> {
+        let connection = match self.connection.as_mut() {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+
+        let log_entries = &game_state.game_log.entries;
+        for log_entry in log_entries[self.sent_log_length.min(log_entries.len())..].iter() {
+            if let LogEntry::ActionExecuted {
+                player_index,
+                description,
+            } = log_entry
+            {
+                connection.send(&ServerMessage::ActionExecuted {
+                    player_name: game_state.players[*player_index].name.clone(),
+                    description: description.clone(),
+                })?;
+            }
+        }
+        self.sent_log_length = log_entries.len();
+
+        let table_view = TableView::new(game_state, player_index);
+        if self.sent_table_view.as_ref() != Some(&table_view) {
+            connection.send(&ServerMessage::Table {
+                table_view: table_view.clone(),
+            })?;
+            self.sent_table_view = Some(table_view);
+        }
+
+        return Ok(());
+    }
+}
+
+// What to do when a player loses their connection
+#[derive(Clone, Copy, Debug)]
+pub struct ReconnectSettings {
+    // How long the game waits for them to come back
+    pub grace_period: Duration,
+    // How long a player has to answer before their connection is treated as
+    // lost. A connection that died without being closed looks just like a
+    // player who's still thinking
+    pub decision_timeout: Duration,
+    // Plays their seat once the grace period is up, until they come back.
+    // Without one the game waits for them however long it takes
+    pub fallback_bot: Option<ControllerKind>,
+}
+
+// The seats played from other computers, by player index. Bots' seats are
+// None
+pub struct RemoteTable {
+    seats: Vec<Option<RemoteSeat>>,
+    joins: Receiver<Join>,
+    reconnect_settings: ReconnectSettings,
+}
+
+impl RemoteTable {
+    // Waits until someone has joined every seat that a person plays
+    pub fn seat_players(
+        listener: TcpListener,
+        players: &[Player],
+        reconnect_settings: ReconnectSettings,
+    ) -> Result<Rc<RefCell<RemoteTable>>, String> {
+        let mut remote_table = RemoteTable {
+            seats: players
+                .iter()
+                .map(|player| {
+                    (player.controller == ControllerKind::Human).then(|| RemoteSeat {
+                        player_name: player.name.clone(),
+                        seat_token: None,
+                        connection: None,
+                        bot_playing: false,
+                        sent_log_length: 0,
+                        sent_table_view: None,
+                    })
+                })
+                .collect(),
+            joins: listen_for_joins(listener),
+            reconnect_settings,
+        };
+
+        loop {
+            let open_seat_count = remote_table
+                .seats
+                .iter()
+                .flatten()
+                .filter(|seat| seat.seat_token.is_none())
+                .count();
+            if open_seat_count == 0 {
+                break;
+            }
+            println!("Waiting for {} more players to join", open_seat_count);
+
+            let join = remote_table
+                .joins
+                .recv()
+                .map_err(|_| "Stopped listening for players".to_string())?;
+            remote_table.seat(join);
+        }
+
+        return Ok(Rc::new(RefCell::new(remote_table)));
+    }
+
+    // Gives a client the seat it asked for, if it can have it. Returns the
+    // seat's player index if it did
+    fn seat(&mut self, join: Join) -> Option<usize> {
+        let Join {
+            mut connection,
+            address,
+            player_name,
+            seat_token,
+        } = join;
+
+        let player_index = self
+            .seats
+            .iter()
+            .position(|seat| match (seat, &seat_token) {
+                (Some(seat), Some(seat_token)) => seat.seat_token.as_ref() == Some(seat_token),
+                (Some(seat), None) => {
+                    seat.seat_token.is_none()
+                        && player_name
+                            .as_ref()
+                            .is_none_or(|player_name| *player_name == seat.player_name)
+                }
+                (None, _) => false,
+            });
+        let Some(player_index) = player_index else {
+            let problem = match (seat_token, player_name) {
+                (Some(_), _) => "That seat token isn't for any seat".to_string(),
+                (None, Some(player_name)) => format!("There's no free seat for {}", player_name),
+                (None, None) => "There are no free seats".to_string(),
+            };
+            eprintln!("{} couldn't join: {}", address, problem);
+            // The client is told why before it's dropped, if it's listening
+            let _ = connection.send(&ServerMessage::Message { text: problem });
+            return None;
+        };
+
+        let seat = self.seats[player_index]
+            .as_mut()
+            .expect("Only people's seats can be joined");
+        let rejoining = seat.seat_token.is_some();
+        let seat_token = seat
+            .seat_token
+            .get_or_insert_with(|| format!("{:016x}", rand::random::<u64>()))
+            .clone();
+
+        let welcome = ServerMessage::Welcome {
+            player_index,
+            player_name: seat.player_name.clone(),
+            seat_token,
+        };
+        if let Err(error) = connection.send(&welcome) {
+            eprintln!("{} couldn't join: {}", address, error);
+            return None;
+        }
+
+        // Any old connection is replaced, and the whole table is sent again
+        seat.connection = Some(connection);
+        seat.bot_playing = false;
+        seat.sent_table_view = None;
+
+        let player_name = seat.player_name.clone();
+        if rejoining {
+            println!("{} rejoined from {}", player_name, address);
+            self.tell_everyone_else(player_index, &format!("{} is back", player_name));
+        } else {
+            println!("{} joined from {}", player_name, address);
+        }
+
+        return Some(player_index);
+    }
+
+    // Seats everyone who has connected since the last time, without waiting
+    // for anyone else. Returns the player indices of the seats taken
+    fn seat_waiting_joins(&mut self) -> Vec<usize> {
+        let mut seated = vec![];
+        while let Ok(join) = self.joins.try_recv() {
+            seated.extend(self.seat(join));
+        }
+
+        return seated;
+    }
+
+    fn connected(&self, player_index: usize) -> bool {
+        self.seats[player_index]
+            .as_ref()
+            .is_some_and(|seat| seat.connection.is_some())
+    }
+
+    fn bot_playing(&self, player_index: usize) -> bool {
+        self.seats[player_index]
+            .as_ref()
+            .is_some_and(|seat| seat.bot_playing)
+    }
+
+    fn disconnect(&mut self, player_index: usize, error: io::Error) {
+        if let Some(seat) = self.seats[player_index].as_mut() {
+            if seat.connection.take().is_some() {
+                eprintln!("Lost the connection to {}: {}", seat.player_name, error);
+            }
+        }
+    }
+
+    fn tell_everyone_else(&mut self, player_index: usize, text: &str) {
+        for other_player_index in 0..self.seats.len() {
+            if other_player_index == player_index {
+                continue;
+            }
+
+            if let Some(connection) = self.seats[other_player_index]
+                .as_mut()
+                .and_then(|seat| seat.connection.as_mut())
+            {
+                let message = ServerMessage::Message { text: text.into() };
+                if let Err(error) = connection.send(&message) {
+                    self.disconnect(other_player_index, error);
+                }
+            }
+        }
+    }
+
+    // Brings every remote player up to date, so they can watch while others
+    // decide
+    fn catch_up_everyone(&mut self, game_state: &GameState) {
+        for player_index in 0..self.seats.len() {
+            if let Some(seat) = self.seats[player_index].as_mut() {
+                if let Err(error) = seat.catch_up(game_state, player_index) {
+                    self.disconnect(player_index, error);
+                }
+            }
+        }
+    }
+
+    // Pauses the game until a disconnected player comes back. Returns false
+    // if the grace period ran out first and the fallback bot should take over
+    fn wait_for_player(&mut self, player_index: usize) -> Result<bool, GameError> {
+        let player_name = match self.seats[player_index].as_ref() {
+            Some(seat) => seat.player_name.clone(),
+            None => return Ok(true),
+        };
+
+        let deadline = self
+            .reconnect_settings
+            .fallback_bot
+            .map(|_| Instant::now() + self.reconnect_settings.grace_period);
+        let waiting = match deadline {
+            Some(_) => format!(
+                "{} lost their connection. Waiting up to {} seconds for them to come back",
+                player_name,
+                self.reconnect_settings.grace_period.as_secs()
+            ),
+            None => format!(
+                "{} lost their connection. Waiting for them to come back",
+                player_name
+            ),
+        };
+        println!("{}", waiting);
+        self.tell_everyone_else(player_index, &waiting);
+
+        while !self.connected(player_index) {
+            let join = match deadline {
+                Some(deadline) => {
+                    let time_left = deadline.saturating_duration_since(Instant::now());
+                    match self.joins.recv_timeout(time_left) {
+                        Ok(join) => join,
+                        Err(RecvTimeoutError::Timeout) => return Ok(false),
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match self.joins.recv() {
+                    Ok(join) => join,
+                    Err(_) => break,
+                },
+            };
+            self.seat(join);
+        }
+
+        if !self.connected(player_index) {
+            return Err(GameError::Invariant(
+                "Stopped listening for players".to_string(),
+            ));
+        }
+
+        return Ok(true);
+    }
+
+    // Hands a disconnected player's seat to the fallback bot
+    fn start_fallback_bot(&mut self, player_index: usize, fallback_bot: ControllerKind) {
+        if let Some(seat) = self.seats[player_index].as_mut() {
+            seat.bot_playing = true;
+            let taking_over = format!(
+                "A bot ({}) is playing for {} until they come back",
+                fallback_bot, seat.player_name
+            );
+            println!("{}", taking_over);
+            self.tell_everyone_else(player_index, &taking_over);
+        }
+    }
+
+    // Sends the request and waits for the answer, seating anyone who joins
+    // meanwhile. Anything else the client sends is turned down and the
+    // request sent again. If the player rejoins while they're deciding
+    // they're asked again on their new connection
+    fn request<T>(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        request: &ServerMessage,
+        answer: &impl Fn(ClientMessage) -> Option<T>,
+    ) -> io::Result<T> {
+        let decision_timeout = self.reconnect_settings.decision_timeout;
+        let mut deadline = Instant::now() + decision_timeout;
+        let mut request_sent = false;
+        loop {
+            let seated = self.seat_waiting_joins();
+            if !seated.is_empty() {
+                self.catch_up_everyone(game_state);
+            }
+            if seated.contains(&player_index) {
+                deadline = Instant::now() + decision_timeout;
+                request_sent = false;
+            }
+
+            let connection = self.seats[player_index]
+                .as_mut()
+                .and_then(|seat| seat.connection.as_mut())
+                .ok_or(io::ErrorKind::NotConnected)?;
+            if !request_sent {
+                connection.send(request)?;
+                request_sent = true;
+            }
+
+            let problem = match connection.try_receive::<ClientMessage>()? {
+                Some(Ok(client_message)) => match answer(client_message.clone()) {
+                    Some(answer) => return Ok(answer),
+                    None => format!(
+                        "That doesn't answer the question. Given: {:?}",
+                        client_message
+                    ),
+                },
+                Some(Err(problem)) => problem,
+                None if Instant::now() >= deadline => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("No answer after {} seconds", decision_timeout.as_secs()),
+                    ))
+                }
+                None => continue,
+            };
+            connection.send(&ServerMessage::Message { text: problem })?;
+            request_sent = false;
+        }
+    }
+
+    // Tells everyone how the game ended, or why it stopped
+    pub fn game_over(&mut self, game_state: &GameState, result: &Result<GameResult, GameError>) {
+        self.seat_waiting_joins();
+        self.catch_up_everyone(game_state);
+
+        let message = match result {
+            Ok(game_result) => ServerMessage::GameOver {
+                finishing_order: game_result
+                    .finishing_order
+                    .iter()
+                    .map(|player_index| game_state.players[*player_index].name.clone())
+                    .collect(),
+                turn_count: game_result.turn_count,
+            },
+            Err(error) => ServerMessage::Message {
+                text: format!("The game stopped: {}", error),
+            },
+        };
+        for seat in self.seats.iter_mut().flatten() {
+            if let Some(connection) = seat.connection.as_mut() {
+                let _ = connection.send(&message);
+            }
+        }
+    }
+}
+
+// A person playing a seat from another computer. The server holds the only
+// copy of the game, and checks every choice they send the same way it checks
+// a person at the terminal. If they lose their connection the game waits for
+// them to come back, then hands their seat to the fallback bot if there is one
+pub struct NetworkController {
+    remote_table: Rc<RefCell<RemoteTable>>,
+    player_index: usize,
+    // Made the first time the player's grace period runs out
+    fallback_bot: Option<Box<dyn PlayerController>>,
+}
+
+impl NetworkController {
+    pub fn new(remote_table: &Rc<RefCell<RemoteTable>>, player_index: usize) -> Self {
+        NetworkController {
+            remote_table: Rc::clone(remote_table),
+            player_index,
+            fallback_bot: None,
+        }
+    }
+
+    // Catches everyone up and asks the player, or the fallback bot while it's
+    // playing for them
+    fn ask<T>(
+        &mut self,
+        game_state: &GameState,
+        request: &ServerMessage,
+        answer: impl Fn(ClientMessage) -> Option<T>,
+        ask_bot: impl FnOnce(&mut dyn PlayerController) -> Result<T, GameError>,
+    ) -> Result<T, GameError> {
+        let player_index = self.player_index;
+        loop {
+            let mut remote_table = self.remote_table.borrow_mut();
+            remote_table.seat_waiting_joins();
+            remote_table.catch_up_everyone(game_state);
+
+            if remote_table.connected(player_index) {
+                match remote_table.request(game_state, player_index, request, &answer) {
+                    Ok(answer) => return Ok(answer),
+                    Err(error) => {
+                        remote_table.disconnect(player_index, error);
+                        continue;
+                    }
+                }
+            }
+
+            if !remote_table.bot_playing(player_index) {
+                if remote_table.wait_for_player(player_index)? {
+                    // They're back, so they're caught up before they're asked
+                    continue;
+                }
+
+                let fallback_bot = remote_table
+                    .reconnect_settings
+                    .fallback_bot
+                    .ok_or_else(|| GameError::Invariant("There's no fallback bot".into()))?;
+                if self.fallback_bot.is_none() {
+                    self.fallback_bot = bot_for(fallback_bot, player_index, game_state.seed);
+                }
+                remote_table.start_fallback_bot(player_index, fallback_bot);
+            }
+
+            // The bot might take a while, and people can rejoin meanwhile
+            drop(remote_table);
+            let fallback_bot = self
+                .fallback_bot
+                .as_deref_mut()
+                .ok_or_else(|| GameError::Invariant("The fallback bot has to be a bot".into()))?;
+            return ask_bot(fallback_bot);
+        }
+    }
+}
+
+impl PlayerController for NetworkController {
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        options: &[ActionOption],
+    ) -> Result<usize, GameError> {
+        let request = ServerMessage::ChooseAction {
+            options: options.iter().map(ToString::to_string).collect(),
+        };
+
+        return self.ask(
+            game_state,
+            &request,
+            |client_message| match client_message {
+                ClientMessage::ChooseAction { option_index } => Some(option_index),
+                _ => None,
+            },
+            |fallback_bot| fallback_bot.choose_action(game_state, player_index, options),
+        );
+    }
+
+    fn choose_cards(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError> {
+        let request = ServerMessage::ChooseCards {
+            source: card_choice.source.clone(),
+            destination: card_choice.destination.clone(),
+            cards: card_choice.visible_source_cards.clone(),
+            required_count: card_choice.required_count,
+        };
+
+        return self.ask(
+            game_state,
+            &request,
+            |client_message| match client_message {
+                ClientMessage::ChooseCards { card_indices } => Some(card_indices),
+                _ => None,
+            },
+            |fallback_bot| fallback_bot.choose_cards(game_state, player_index, card_choice),
+        );
+    }
+
+    fn show_message(&mut self, game_state: &GameState, player_index: usize, message: &str) {
+        let mut remote_table = self.remote_table.borrow_mut();
+        if remote_table.bot_playing(player_index) {
+            drop(remote_table);
+            if let Some(fallback_bot) = self.fallback_bot.as_mut() {
+                fallback_bot.show_message(game_state, player_index, message);
+            }
+            return;
+        }
+
+        if let Some(connection) = remote_table.seats[player_index]
+            .as_mut()
+            .and_then(|seat| seat.connection.as_mut())
+        {
+            let message = ServerMessage::Message {
+                text: message.to_string(),
+            };
+            if let Err(error) = connection.send(&message) {
+                remote_table.disconnect(player_index, error);
+            }
+        }
+    }
+
+    // Only while the fallback bot is playing, and only if it needs it
+    fn wants_action_start(&self) -> bool {
+        return self.remote_table.borrow().bot_playing(self.player_index)
+            && self
+                .fallback_bot
+                .as_ref()
+                .is_some_and(|fallback_bot| fallback_bot.wants_action_start());
+    }
+}
+
+// Bots play their seats on the server and everyone else plays from their own
+// computer
+pub fn controllers_for(
+    players: &[Player],
+    seed: u64,
+    remote_table: &Rc<RefCell<RemoteTable>>,
+) -> Vec<Box<dyn PlayerController>> {
+    return players
+        .iter()
+        .enumerate()
+        .map(|(player_index, player)| {
+            bot_for(player.controller, player_index, seed)
+                .unwrap_or_else(|| Box::new(NetworkController::new(remote_table, player_index)))
+        })
+        .collect();
+}
+
+// Plays a seat in a game hosted somewhere else, from this terminal. A seat
+// token takes back a seat after losing the connection. Returns the finishing
+// order and turn count once the game is over
+pub fn connect(
+    address: &str,
+    player_name: Option<String>,
+    seat_token: Option<String>,
+) -> Result<(Vec<String>, usize), String> {
+    let stream = TcpStream::connect(address)
+        .map_err(|error| format!("Couldn't connect to {}: {}", address, error))?;
+    let mut connection = Connection::new(stream).map_err(|error| error.to_string())?;
+    let lost_connection =
+        |error: io::Error| format!("Lost the connection to {}: {}", address, error);
+
+    connection
+        .send(&ClientMessage::Join {
+            player_name,
+            seat_token,
+        })
+        .map_err(lost_connection)?;
+
+    loop {
+        let server_message = connection
+            .receive::<ServerMessage>()
+            .map_err(lost_connection)??;
+
+        match server_message {
+            ServerMessage::Welcome {
+                player_name,
+                seat_token,
+                ..
+            } => println!(
+                "Joined {} as {}. Rejoin with --seat-token {} if the connection drops",
+                address, player_name, seat_token
+            ),
+            ServerMessage::ActionExecuted {
+                player_name,
+                description,
+            } => println!("{}: {}", player_name, description),
+            ServerMessage::Table { table_view } => {
+                println!();
+                println!("{}", table_view);
+            }
+            ServerMessage::ChooseAction { options } => {
+                let option_index =
+                    user_input::read_action_choice(&options).map_err(|error| error.to_string())?;
+                connection
+                    .send(&ClientMessage::ChooseAction { option_index })
+                    .map_err(lost_connection)?;
+            }
+            ServerMessage::ChooseCards {
+                source,
+                destination,
+                cards,
+                ..
+            } => {
+                let card_indices =
+                    user_input::read_card_selection(&source, &destination, cards.len())
+                        .map_err(|error| error.to_string())?;
+                connection
+                    .send(&ClientMessage::ChooseCards { card_indices })
+                    .map_err(lost_connection)?;
+            }
+            ServerMessage::Message { text } => println!("{}", text),
+            ServerMessage::GameOver {
+                finishing_order,
+                turn_count,
+            } => return Ok((finishing_order, turn_count)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg64;
+
+    use super::*;
+    use crate::game_rules::poo_head_rules;
+
+    // A client that plays its seat by picking at random, much like someone
+    // at the terminal who doesn't know the rules
+    struct TestClient {
+        connection: Connection,
+        seat_token: String,
+        rng: Pcg64,
+        answer_count: usize,
+        messages: Vec<String>,
+    }
+
+    impl TestClient {
+        fn join(address: SocketAddr, player_name: &str, seat_token: Option<String>) -> Self {
+            let stream = TcpStream::connect(address).unwrap();
+            // A server that stops talking fails the test rather than hanging it
+            stream
+                .set_read_timeout(Some(Duration::from_secs(30)))
+                .unwrap();
+            let mut connection = Connection::new(stream).unwrap();
+            connection
+                .send(&ClientMessage::Join {
+                    player_name: Some(player_name.to_string()),
+                    seat_token,
+                })
+                .unwrap();
+
+            let seat_token = match connection.receive::<ServerMessage>().unwrap().unwrap() {
+                ServerMessage::Welcome { seat_token, .. } => seat_token,
+                server_message => panic!("Expected a welcome. Given: {:?}", server_message),
+            };
+
+            return TestClient {
+                connection,
+                seat_token,
+                rng: Pcg64::seed_from_u64(player_name.len() as u64),
+                answer_count: 0,
+                messages: vec![],
+            };
+        }
+
+        // Answers every request until the game is over and returns the
+        // finishing order. The first answer is always out of range, so the
+        // server has to turn it down and ask again
+        fn play(&mut self) -> Vec<String> {
+            return self.play_until(None).expect("The game isn't over");
+        }
+
+        // Like play, but drops the connection instead of giving answer number
+        // drop_at, and returns None
+        fn play_until(&mut self, drop_at: Option<usize>) -> Option<Vec<String>> {
+            loop {
+                let client_message = match self.connection.receive().unwrap().unwrap() {
+                    ServerMessage::ChooseAction { options } => ClientMessage::ChooseAction {
+                        option_index: match self.answer_count {
+                            0 => options.len(),
+                            _ => self.rng.gen_range(0, options.len()),
+                        },
+                    },
+                    ServerMessage::ChooseCards {
+                        cards,
+                        required_count,
+                        ..
+                    } => {
+                        let mut card_indices = (0..cards.len()).collect::<Vec<usize>>();
+                        card_indices.shuffle(&mut self.rng);
+                        card_indices.truncate(required_count.unwrap_or(1));
+                        if self.answer_count == 0 {
+                            card_indices = vec![cards.len()];
+                        }
+                        ClientMessage::ChooseCards { card_indices }
+                    }
+                    ServerMessage::Message { text } => {
+                        self.messages.push(text);
+                        continue;
+                    }
+                    ServerMessage::GameOver {
+                        finishing_order, ..
+                    } => return Some(finishing_order),
+                    _ => continue,
+                };
+
+                if drop_at == Some(self.answer_count) {
+                    return None;
+                }
+                self.answer_count += 1;
+                self.connection.send(&client_message).unwrap();
+            }
+        }
+    }
+
+    fn serve_game(
+        listener: TcpListener,
+        player_specs: &[&str],
+        reconnect_settings: ReconnectSettings,
+    ) -> (GameState, Result<GameResult, GameError>) {
+        let players = player_specs
+            .iter()
+            .map(|player_spec| Player::from_spec(player_spec).unwrap())
+            .collect();
+        let mut game_state = GameState::new(poo_head_rules(), players, 3);
+        game_state.deal();
+        game_state.turn_limit = Some(5000);
+        game_state.decision_limit = Some(100_000);
+
+        let remote_table =
+            RemoteTable::seat_players(listener, &game_state.players, reconnect_settings).unwrap();
+        let mut controllers = controllers_for(&game_state.players, game_state.seed, &remote_table);
+        let result = game_state.play_game(&mut controllers);
+        remote_table.borrow_mut().game_over(&game_state, &result);
+
+        return (game_state, result);
+    }
+
+    fn finishing_names(game_state: &GameState, game_result: &GameResult) -> Vec<String> {
+        game_result
+            .finishing_order
+            .iter()
+            .map(|player_index| game_state.players[*player_index].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn clients_play_a_game_held_by_the_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let ann = thread::spawn(move || TestClient::join(address, "Ann", None).play());
+        let bob = thread::spawn(move || {
+            let mut bob = TestClient::join(address, "Bob", None);
+            let finishing_order = bob.play();
+            return (finishing_order, bob.messages);
+        });
+
+        let (game_state, result) = serve_game(
+            listener,
+            &["Ann", "Bob", "Cat:random"],
+            ReconnectSettings {
+                grace_period: Duration::from_secs(60),
+                decision_timeout: Duration::from_secs(30),
+                fallback_bot: None,
+            },
+        );
+
+        let game_result = result.unwrap();
+        let finishing_order = finishing_names(&game_state, &game_result);
+        assert_eq!(ann.join().unwrap(), finishing_order);
+        let (bob_finishing_order, bob_messages) = bob.join().unwrap();
+        assert_eq!(bob_finishing_order, finishing_order);
+        // The server checks every choice, so the out of range one was turned
+        // down
+        assert!(bob_messages
+            .iter()
+            .any(|message| message.starts_with("Invalid")));
+    }
+
+    #[test]
+    fn players_who_drop_take_their_seat_back_with_its_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // Someone who connects and never says which seat they want doesn't
+        // keep anyone else waiting
+        let _silent_stream = TcpStream::connect(address).unwrap();
+
+        let ann = thread::spawn(move || {
+            let mut ann = TestClient::join(address, "Ann", None);
+            assert_eq!(ann.play_until(Some(20)), None);
+            let seat_token = ann.seat_token.clone();
+            drop(ann);
+
+            let mut ann = TestClient::join(address, "Ann", Some(seat_token.clone()));
+            assert_eq!(ann.seat_token, seat_token);
+            return ann.play();
+        });
+        let bob = thread::spawn(move || {
+            let mut bob = TestClient::join(address, "Bob", None);
+            let finishing_order = bob.play();
+            return (finishing_order, bob.messages);
+        });
+
+        let started_at = Instant::now();
+        let (game_state, result) = serve_game(
+            listener,
+            &["Ann", "Bob"],
+            ReconnectSettings {
+                grace_period: Duration::from_secs(60),
+                decision_timeout: Duration::from_secs(30),
+                fallback_bot: None,
+            },
+        );
+        assert!(started_at.elapsed() < JOIN_TIMEOUT);
+
+        let game_result = result.unwrap();
+        let finishing_order = finishing_names(&game_state, &game_result);
+        assert_eq!(ann.join().unwrap(), finishing_order);
+        let (bob_finishing_order, bob_messages) = bob.join().unwrap();
+        assert_eq!(bob_finishing_order, finishing_order);
+        assert!(bob_messages.contains(&"Ann is back".to_string()));
+    }
+
+    #[test]
+    fn players_who_stop_answering_are_played_by_the_fallback_bot() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // Joins and reads what it's sent, but never answers, like a
+        // connection that died without being closed
+        let ann = thread::spawn(move || {
+            let mut ann = TestClient::join(address, "Ann", None);
+            let mut request_count = 0;
+            loop {
+                match ann.connection.receive::<ServerMessage>() {
+                    Ok(Ok(ServerMessage::ChooseAction { .. }))
+                    | Ok(Ok(ServerMessage::ChooseCards { .. })) => request_count += 1,
+                    Ok(_) => {}
+                    Err(error) => {
+                        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+                        return request_count;
+                    }
+                }
+            }
+        });
+
+        let (_, result) = serve_game(
+            listener,
+            &["Ann", "Bob:random"],
+            ReconnectSettings {
+                grace_period: Duration::ZERO,
+                decision_timeout: Duration::from_millis(200),
+                fallback_bot: Some(ControllerKind::RandomBot),
+            },
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(ann.join().unwrap(), 1);
+    }
+}
@@ -1,9 +1,95 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
 
 use crate::card_deck::CardGroup;
+use crate::heuristic_bot::Difficulty;
+use crate::mcts_bot::SearchBudget;
+
+// Who makes the decisions for a player
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ControllerKind {
+    #[default]
+    Human,
+    RandomBot,
+    HeuristicBot(Difficulty),
+    MctsBot(SearchBudget),
+}
+
+impl fmt::Display for ControllerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControllerKind::Human => write!(f, "human"),
+            ControllerKind::RandomBot => write!(f, "random"),
+            ControllerKind::HeuristicBot(difficulty) => write!(f, "heuristic:{}", difficulty),
+            ControllerKind::MctsBot(search_budget) => write!(f, "mcts:{}", search_budget),
+        }
+    }
+}
+
+impl FromStr for ControllerKind {
+    type Err = String;
 
-#[derive(Debug)]
+    // Bot settings come after another colon, eg. "heuristic:hard" or
+    // "mcts:250ms"
+    fn from_str(controller_kind: &str) -> Result<Self, Self::Err> {
+        let (kind, settings) = match controller_kind.split_once(':') {
+            Some((kind, settings)) => (kind, Some(settings)),
+            None => (controller_kind, None),
+        };
+
+        match (kind, settings) {
+            ("human", None) => Ok(ControllerKind::Human),
+            ("random", None) => Ok(ControllerKind::RandomBot),
+            ("heuristic", None) => Ok(ControllerKind::HeuristicBot(Default::default())),
+            ("heuristic", Some(difficulty)) => {
+                Ok(ControllerKind::HeuristicBot(difficulty.parse()?))
+            }
+            ("mcts", None) => Ok(ControllerKind::MctsBot(Default::default())),
+            ("mcts", Some(search_budget)) => Ok(ControllerKind::MctsBot(search_budget.parse()?)),
+            _ => Err(format!(
+                "Unknown kind of player: {}. Use human, random, heuristic or mcts",
+                controller_kind
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Player {
     pub name: String,
-    pub hand: HashMap<String, CardGroup>,
+    pub hand: BTreeMap<String, CardGroup>,
+
+    #[serde(default)]
+    pub controller: ControllerKind,
+}
+
+impl Player {
+    // Players are given as "name" for a person or "name:kind" for a bot, eg.
+    // "Robo:random" or "Robo:heuristic:hard"
+    pub fn from_spec(player_spec: &str) -> Result<Self, String> {
+        let (name, controller) = match player_spec.split_once(':') {
+            Some((name, controller_kind)) => (name, controller_kind.parse()?),
+            None => (player_spec, ControllerKind::Human),
+        };
+
+        if name.is_empty() {
+            return Err(format!("Players need a name. Given: {}", player_spec));
+        }
+
+        return Ok(Player {
+            name: name.into(),
+            hand: BTreeMap::new(),
+            controller,
+        });
+    }
+
+    pub fn card_count(&self) -> usize {
+        self.hand
+            .values()
+            .map(|hand_card_group| hand_card_group.cards.len())
+            .sum()
+    }
 }
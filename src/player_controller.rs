@@ -0,0 +1,351 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::card_deck::Card;
+use crate::game_error::{GameError, InputError};
+use crate::game_log::LogEntry;
+use crate::game_rules::{Action, CardMove};
+use crate::game_state::GameState;
+use crate::heuristic_bot::{HeuristicController, HeuristicWeights};
+use crate::mcts_bot::{MctsController, MctsSettings};
+use crate::player::{ControllerKind, Player};
+use crate::random_bot::RandomController;
+use crate::user_input::{Terminal, TerminalController};
+
+// Something a player can pick when it's their decision
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActionOption<'a> {
+    Action(&'a Action),
+    // Pass play on once the turn's phases are over
+    EndTurn,
+    // Take back the last action this turn
+    Undo,
+}
+
+impl fmt::Display for ActionOption<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionOption::Action(action) => write!(f, "{}", action),
+            ActionOption::EndTurn => write!(f, "End turn"),
+            ActionOption::Undo => write!(f, "Undo last action"),
+        }
+    }
+}
+
+// A choice of cards to move out of one card group into another. Choices are
+// indices into the source card group. It only has the cards the choosing
+// player can see, so nothing worked out from it can give away the rest
+pub struct CardChoice<'a> {
+    pub source: String,
+    pub destination: String,
+    // What the choosing player can see of the source and destination cards,
+    // in card group order. Hidden cards are None
+    pub visible_source_cards: Vec<Option<Card>>,
+    pub visible_destination_cards: Vec<Option<Card>>,
+    // Moves check the chosen cards against their card conditions. Swaps can
+    // choose any cards, including none
+    card_move: Option<&'a CardMove>,
+    // The second half of a swap has to move as many cards as the first
+    pub required_count: Option<usize>,
+}
+
+// Enumerating selections stops here so big card groups can't blow up
+const MAX_ALLOWED_SELECTIONS: usize = 10_000;
+
+impl<'a> CardChoice<'a> {
+    pub fn new(
+        source: String,
+        destination: String,
+        visible_source_cards: Vec<Option<Card>>,
+        visible_destination_cards: Vec<Option<Card>>,
+        card_move: Option<&'a CardMove>,
+    ) -> Self {
+        CardChoice {
+            source,
+            destination,
+            visible_source_cards,
+            visible_destination_cards,
+            card_move,
+            required_count: None,
+        }
+    }
+
+    pub fn card_count(&self) -> usize {
+        self.visible_source_cards.len()
+    }
+
+    // Swaps choose cards out of each card group in turn, rather than playing
+    // cards that have to meet conditions
+    pub fn is_swap(&self) -> bool {
+        self.card_move.is_none()
+    }
+
+    // Problems that adding more cards can't fix: bad indices and broken card
+    // conditions
+    fn conditions_problem(
+        &self,
+        card_indices: &[usize],
+        game_state: &GameState,
+    ) -> Result<Option<String>, GameError> {
+        let mut selected_card_indices = HashSet::new();
+        for card_index in card_indices.iter() {
+            if *card_index >= self.card_count() {
+                return Ok(Some(
+                    InputError::InvalidCardIndex {
+                        index: *card_index as isize,
+                        card_count: self.card_count(),
+                    }
+                    .to_string(),
+                ));
+            }
+
+            if !selected_card_indices.insert(card_index) {
+                return Ok(Some(format!("card {} is selected twice", card_index)));
+            }
+        }
+
+        // Only the cards the player can see are held to the card conditions.
+        // Hidden cards are checked once they're played
+        if let Some(card_move) = self.card_move {
+            let selected_visible_cards = card_indices
+                .iter()
+                .filter_map(|card_index| self.visible_source_cards[*card_index].clone())
+                .collect::<Vec<Card>>();
+            return card_move.selection_problem(&selected_visible_cards, game_state);
+        }
+
+        Ok(None)
+    }
+
+    // Describes why a selection can't be made, or None if it can
+    pub fn selection_problem(
+        &self,
+        card_indices: &[usize],
+        game_state: &GameState,
+    ) -> Result<Option<String>, GameError> {
+        if let Some(conditions_problem) = self.conditions_problem(card_indices, game_state)? {
+            return Ok(Some(conditions_problem));
+        }
+
+        if self.card_move.is_some() && card_indices.is_empty() {
+            return Ok(Some("at least one card must be selected".into()));
+        }
+
+        if let Some(required_count) = self.required_count {
+            if card_indices.len() != required_count {
+                return Ok(Some(
+                    InputError::UnevenCardSwap {
+                        first_count: required_count,
+                        second_count: card_indices.len(),
+                    }
+                    .to_string(),
+                ));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Every selection the choice allows, each in increasing index order. They
+    // are found by adding one card at a time to selections whose cards meet
+    // the card conditions, which finds them all because any part of a
+    // selection meeting the conditions meets them too
+    pub fn allowed_selections(&self, game_state: &GameState) -> Result<Vec<Vec<usize>>, GameError> {
+        let mut allowed_selections = vec![];
+        let mut partial_selections = vec![vec![]];
+
+        while let Some(partial_selection) = partial_selections.pop() {
+            if allowed_selections.len() >= MAX_ALLOWED_SELECTIONS {
+                break;
+            }
+
+            if self
+                .selection_problem(&partial_selection, game_state)?
+                .is_none()
+            {
+                allowed_selections.push(partial_selection.clone());
+            }
+
+            if Some(partial_selection.len()) == self.required_count {
+                continue;
+            }
+
+            let next_card_index = partial_selection.last().map_or(0, |last| last + 1);
+            for card_index in next_card_index..self.card_count() {
+                let mut selection = partial_selection.clone();
+                selection.push(card_index);
+                if self.conditions_problem(&selection, game_state)?.is_none() {
+                    partial_selections.push(selection);
+                }
+            }
+        }
+
+        return Ok(allowed_selections);
+    }
+
+    // A random selection the choice allows, built up a card at a time. It's
+    // much quicker than picking from allowed_selections for big card groups,
+    // though not every selection is as likely. None if it hit a dead end
+    pub fn random_selection<R: Rng>(
+        &self,
+        game_state: &GameState,
+        rng: &mut R,
+    ) -> Result<Option<Vec<usize>>, GameError> {
+        let mut selection = vec![];
+        loop {
+            let mut next_card_indices = vec![];
+            if Some(selection.len()) != self.required_count {
+                for card_index in 0..self.card_count() {
+                    if selection.contains(&card_index) {
+                        continue;
+                    }
+
+                    let mut next_selection = selection.clone();
+                    next_selection.push(card_index);
+                    if self
+                        .conditions_problem(&next_selection, game_state)?
+                        .is_none()
+                    {
+                        next_card_indices.push(card_index);
+                    }
+                }
+            }
+
+            // Allowed selections that could take more cards stop half the time
+            if self.selection_problem(&selection, game_state)?.is_none()
+                && (next_card_indices.is_empty() || rng.gen_bool(0.5))
+            {
+                selection.sort_unstable();
+                return Ok(Some(selection));
+            }
+
+            match next_card_indices.choose(rng) {
+                Some(card_index) => selection.push(*card_index),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+// Makes the decisions for a seat at the table, eg. a person at the terminal
+// or a bot. The game checks every choice and asks again if it can't be made
+pub trait PlayerController {
+    // Returns an index into the options
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        options: &[ActionOption],
+    ) -> Result<usize, GameError>;
+
+    fn choose_cards(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError>;
+
+    // Eg. why a choice was turned down
+    fn show_message(&mut self, _game_state: &GameState, _player_index: usize, _message: &str) {}
+
+    // Whether the controller needs GameState::action_start while it decides.
+    // Copying the game for it slows every action down, so it's opt in
+    fn wants_action_start(&self) -> bool {
+        false
+    }
+}
+
+// A bot to play a seat, or None for a person. Bots are seeded from the game's
+// seed, so a seeded game with the same choices from people plays out the same
+// way
+pub fn bot_for(
+    controller_kind: ControllerKind,
+    player_index: usize,
+    seed: u64,
+) -> Option<Box<dyn PlayerController>> {
+    let bot_seed = seed.wrapping_add(player_index as u64 + 1);
+    match controller_kind {
+        ControllerKind::Human => None,
+        ControllerKind::RandomBot => Some(Box::new(RandomController::new(bot_seed))),
+        ControllerKind::HeuristicBot(difficulty) => Some(Box::new(HeuristicController::new(
+            HeuristicWeights::for_difficulty(difficulty),
+            bot_seed,
+        ))),
+        ControllerKind::MctsBot(search_budget) => Some(Box::new(MctsController::new(
+            MctsSettings::new(search_budget),
+            bot_seed,
+        ))),
+    }
+}
+
+// A controller for each player's seat, with the people sharing this terminal
+pub fn controllers_for(
+    players: &[Player],
+    seed: u64,
+    hot_seat: bool,
+) -> Vec<Box<dyn PlayerController>> {
+    let terminal = Terminal::new(hot_seat);
+
+    return (0..players.len())
+        .map(|player_index| {
+            bot_for(players[player_index].controller, player_index, seed)
+                .unwrap_or_else(|| Box::new(TerminalController::new(&terminal)))
+        })
+        .collect();
+}
+
+// Makes the same choices that were logged in an earlier game. Asking for more
+// choices than were logged fails with EndOfInput
+pub struct ScriptedController {
+    choices: VecDeque<LogEntry>,
+}
+
+impl ScriptedController {
+    pub fn new(choices: impl IntoIterator<Item = LogEntry>) -> Self {
+        ScriptedController {
+            choices: choices.into_iter().collect(),
+        }
+    }
+
+    fn next_choice(&mut self) -> Result<LogEntry, GameError> {
+        self.choices
+            .pop_front()
+            .ok_or_else(|| InputError::EndOfInput.into())
+    }
+
+    fn mismatch(expected: &str, log_entry: LogEntry) -> GameError {
+        GameError::Invariant(format!(
+            "The scripted choices don't match the game. Expected {}, found: {:?}",
+            expected, log_entry
+        ))
+    }
+}
+
+impl PlayerController for ScriptedController {
+    fn choose_action(
+        &mut self,
+        _game_state: &GameState,
+        _player_index: usize,
+        _options: &[ActionOption],
+    ) -> Result<usize, GameError> {
+        match self.next_choice()? {
+            LogEntry::ActionChosen { action_index, .. } => Ok(action_index),
+            log_entry => Err(ScriptedController::mismatch("an action", log_entry)),
+        }
+    }
+
+    fn choose_cards(
+        &mut self,
+        _game_state: &GameState,
+        _player_index: usize,
+        _card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError> {
+        match self.next_choice()? {
+            LogEntry::CardsChosen { card_indices, .. } => Ok(card_indices),
+            log_entry => Err(ScriptedController::mismatch("cards", log_entry)),
+        }
+    }
+}
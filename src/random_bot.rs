@@ -0,0 +1,61 @@
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+
+use crate::game_error::GameError;
+use crate::game_state::GameState;
+use crate::player_controller::{ActionOption, CardChoice, PlayerController};
+
+// Picks uniformly among everything it's allowed to do. It's the weakest
+// opponent, and plays any rules file, so it's handy for trying new rules out
+pub struct RandomController {
+    rng: Pcg64,
+}
+
+impl RandomController {
+    pub fn new(seed: u64) -> Self {
+        RandomController {
+            rng: Pcg64::seed_from_u64(seed),
+        }
+    }
+}
+
+impl PlayerController for RandomController {
+    // Never undoes, which could go back and forth forever
+    fn choose_action(
+        &mut self,
+        _game_state: &GameState,
+        _player_index: usize,
+        options: &[ActionOption],
+    ) -> Result<usize, GameError> {
+        let option_indices = options
+            .iter()
+            .enumerate()
+            .filter(|(_, option)| **option != ActionOption::Undo)
+            .map(|(option_index, _)| option_index)
+            .collect::<Vec<usize>>();
+
+        return option_indices
+            .choose(&mut self.rng)
+            .copied()
+            .ok_or_else(|| GameError::Invariant("No options to choose from".into()));
+    }
+
+    fn choose_cards(
+        &mut self,
+        game_state: &GameState,
+        _player_index: usize,
+        card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError> {
+        return card_choice
+            .allowed_selections(game_state)?
+            .choose(&mut self.rng)
+            .cloned()
+            .ok_or_else(|| {
+                GameError::Invariant(format!(
+                    "No cards can be moved from {} into {}",
+                    card_choice.source, card_choice.destination
+                ))
+            });
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use serde::Serialize;
+
+use crate::game_error::GameError;
+use crate::game_log::LogEntry;
+use crate::game_rules::GameRules;
+use crate::game_state::GameState;
+use crate::player::Player;
+use crate::player_controller::controllers_for;
+
+pub struct SimulationSettings {
+    pub game_count: usize,
+    // Each game after the first uses the next seed
+    pub first_seed: u64,
+    pub thread_count: usize,
+    // Games still going after this many turns count as stalled
+    pub turn_limit: usize,
+    // So do games whose players have been asked this many times, which
+    // catches turns that never end
+    pub decision_limit: usize,
+}
+
+// How one simulated game went
+struct GameOutcome {
+    // None if the game stalled
+    finishing_order: Option<Vec<usize>>,
+    turn_count: usize,
+    action_counts: BTreeMap<String, usize>,
+}
+
+fn simulate_game(
+    game_rules: &GameRules,
+    players: &[Player],
+    seed: u64,
+    settings: &SimulationSettings,
+) -> Result<GameOutcome, GameError> {
+    let mut game_state = GameState::new(game_rules.clone(), players.to_vec(), seed);
+    game_state.deal();
+    game_state.turn_limit = Some(settings.turn_limit);
+    game_state.decision_limit = Some(settings.decision_limit);
+
+    let mut controllers = controllers_for(players, seed, false);
+    let finishing_order = match game_state.play_game(&mut controllers) {
+        Ok(game_result) => Some(game_result.finishing_order),
+        Err(GameError::TurnLimitReached(_)) | Err(GameError::DecisionLimitReached(_)) => None,
+        Err(error) => return Err(error),
+    };
+
+    let mut action_counts = BTreeMap::new();
+    for log_entry in game_state.game_log.entries.iter() {
+        if let LogEntry::ActionExecuted { description, .. } = log_entry {
+            *action_counts.entry(description.clone()).or_insert(0) += 1;
+        }
+    }
+
+    return Ok(GameOutcome {
+        finishing_order,
+        turn_count: game_state.turn_count,
+        action_counts,
+    });
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeatStats {
+    pub name: String,
+    pub controller: String,
+    pub wins: usize,
+    pub win_rate: f64,
+    // Counting from 1 for first, over the games that finished
+    pub average_place: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ControllerStats {
+    pub controller: String,
+    // Games played summed over every seat with this kind of bot
+    pub seat_games: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+}
+
+// Turn counts of the games that finished
+#[derive(Debug, Serialize)]
+pub struct TurnCountStats {
+    pub min: usize,
+    pub lower_quartile: usize,
+    pub median: usize,
+    pub upper_quartile: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+impl TurnCountStats {
+    fn new(mut turn_counts: Vec<usize>) -> Option<Self> {
+        if turn_counts.is_empty() {
+            return None;
+        }
+
+        turn_counts.sort_unstable();
+        let quantile = |fraction: f64| {
+            turn_counts[((turn_counts.len() - 1) as f64 * fraction).round() as usize]
+        };
+
+        return Some(TurnCountStats {
+            min: quantile(0.0),
+            lower_quartile: quantile(0.25),
+            median: quantile(0.5),
+            upper_quartile: quantile(0.75),
+            max: quantile(1.0),
+            mean: turn_counts.iter().sum::<usize>() as f64 / turn_counts.len() as f64,
+        });
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationReport {
+    pub game_count: usize,
+    pub first_seed: u64,
+    pub stalled_count: usize,
+    pub turn_limit: usize,
+    pub decision_limit: usize,
+    pub seats: Vec<SeatStats>,
+    pub controllers: Vec<ControllerStats>,
+    pub turn_counts: Option<TurnCountStats>,
+    // How many times each action ran over every game, including consequences
+    pub action_counts: BTreeMap<String, usize>,
+}
+
+impl SimulationReport {
+    fn new(players: &[Player], settings: &SimulationSettings, outcomes: &[GameOutcome]) -> Self {
+        let game_count = outcomes.len();
+        let rate = |count: usize, out_of: usize| count as f64 / out_of.max(1) as f64;
+
+        let mut seats = vec![];
+        for (player_index, player) in players.iter().enumerate() {
+            let places = outcomes
+                .iter()
+                .filter_map(|outcome| outcome.finishing_order.as_ref())
+                .filter_map(|finishing_order| {
+                    finishing_order
+                        .iter()
+                        .position(|finisher| *finisher == player_index)
+                })
+                .collect::<Vec<usize>>();
+            let wins = places.iter().filter(|place| **place == 0).count();
+
+            seats.push(SeatStats {
+                name: player.name.clone(),
+                controller: player.controller.to_string(),
+                wins,
+                win_rate: rate(wins, game_count),
+                average_place: (!places.is_empty()).then(|| {
+                    places.iter().map(|place| place + 1).sum::<usize>() as f64 / places.len() as f64
+                }),
+            });
+        }
+
+        let mut controller_wins: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for seat in seats.iter() {
+            let (seat_games, wins) = controller_wins.entry(seat.controller.clone()).or_default();
+            *seat_games += game_count;
+            *wins += seat.wins;
+        }
+        let controllers = controller_wins
+            .into_iter()
+            .map(|(controller, (seat_games, wins))| ControllerStats {
+                controller,
+                seat_games,
+                wins,
+                win_rate: rate(wins, seat_games),
+            })
+            .collect();
+
+        let mut action_counts = BTreeMap::new();
+        for outcome in outcomes.iter() {
+            for (description, count) in outcome.action_counts.iter() {
+                *action_counts.entry(description.clone()).or_insert(0) += count;
+            }
+        }
+
+        return SimulationReport {
+            game_count,
+            first_seed: settings.first_seed,
+            stalled_count: outcomes
+                .iter()
+                .filter(|outcome| outcome.finishing_order.is_none())
+                .count(),
+            turn_limit: settings.turn_limit,
+            decision_limit: settings.decision_limit,
+            seats,
+            controllers,
+            turn_counts: TurnCountStats::new(
+                outcomes
+                    .iter()
+                    .filter(|outcome| outcome.finishing_order.is_some())
+                    .map(|outcome| outcome.turn_count)
+                    .collect(),
+            ),
+            action_counts,
+        };
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |count: usize| 100.0 * count as f64 / self.game_count.max(1) as f64;
+
+        writeln!(
+            f,
+            "Played {} games with seeds {} onwards",
+            self.game_count, self.first_seed
+        )?;
+        writeln!(
+            f,
+            "Stalled after {} turns or {} decisions: {} ({:.1}%)",
+            self.turn_limit,
+            self.decision_limit,
+            self.stalled_count,
+            percent(self.stalled_count)
+        )?;
+
+        writeln!(f)?;
+        writeln!(f, "Wins by seat:")?;
+        for (seat_index, seat) in self.seats.iter().enumerate() {
+            write!(
+                f,
+                "{}. {} ({}): {} ({:.1}%)",
+                seat_index + 1,
+                seat.name,
+                seat.controller,
+                seat.wins,
+                100.0 * seat.win_rate
+            )?;
+            match seat.average_place {
+                Some(average_place) => writeln!(f, ", average place {:.2}", average_place)?,
+                None => writeln!(f)?,
+            }
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Wins by bot:")?;
+        for controller in self.controllers.iter() {
+            writeln!(
+                f,
+                "{}: {} of {} seat games ({:.1}%)",
+                controller.controller,
+                controller.wins,
+                controller.seat_games,
+                100.0 * controller.win_rate
+            )?;
+        }
+
+        writeln!(f)?;
+        match &self.turn_counts {
+            Some(turn_counts) => writeln!(
+                f,
+                "Turns per finished game: min {}, lower quartile {}, median {}, upper quartile {}, max {}, mean {:.1}",
+                turn_counts.min,
+                turn_counts.lower_quartile,
+                turn_counts.median,
+                turn_counts.upper_quartile,
+                turn_counts.max,
+                turn_counts.mean
+            )?,
+            None => writeln!(f, "No games finished")?,
+        }
+
+        writeln!(f)?;
+        write!(f, "Actions taken:")?;
+        for (description, count) in self.action_counts.iter() {
+            write!(
+                f,
+                "\n{}: {} ({:.1} per game)",
+                description,
+                count,
+                *count as f64 / self.game_count.max(1) as f64
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// Plays every game with the same seats, spread over the threads. Each thread
+// takes the next game nobody has started yet
+pub fn simulate(
+    game_rules: &GameRules,
+    players: &[Player],
+    settings: &SimulationSettings,
+) -> Result<SimulationReport, String> {
+    let thread_count = settings.thread_count.clamp(1, settings.game_count.max(1));
+    let next_game_index = AtomicUsize::new(0);
+
+    let mut outcomes = thread::scope(|scope| {
+        let threads = (0..thread_count)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut outcomes = vec![];
+                    loop {
+                        let game_index = next_game_index.fetch_add(1, Ordering::Relaxed);
+                        if game_index >= settings.game_count {
+                            return outcomes;
+                        }
+
+                        let seed = settings.first_seed.wrapping_add(game_index as u64);
+                        let outcome = simulate_game(game_rules, players, seed, settings)
+                            .map_err(|error| format!("Game with seed {}: {}", seed, error));
+                        outcomes.push((game_index, outcome));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        threads
+            .into_iter()
+            .flat_map(|thread| thread.join().expect("A simulation thread panicked"))
+            .collect::<Vec<_>>()
+    });
+    outcomes.sort_by_key(|(game_index, _)| *game_index);
+
+    let outcomes = outcomes
+        .into_iter()
+        .map(|(_, outcome)| outcome)
+        .collect::<Result<Vec<GameOutcome>, String>>()?;
+
+    return Ok(SimulationReport::new(players, settings, &outcomes));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_rules::{poo_head_rules, POO_HEAD_RULES_YAML};
+
+    fn seats(player_specs: &[&str]) -> Vec<Player> {
+        player_specs
+            .iter()
+            .map(|player_spec| Player::from_spec(player_spec).unwrap())
+            .collect()
+    }
+
+    fn settings(game_count: usize) -> SimulationSettings {
+        SimulationSettings {
+            game_count,
+            first_seed: 1,
+            thread_count: 2,
+            turn_limit: 1000,
+            decision_limit: 2000,
+        }
+    }
+
+    #[test]
+    fn games_play_to_the_end() {
+        let simulation_report = simulate(
+            &poo_head_rules(),
+            &seats(&["A:heuristic", "B:random"]),
+            &settings(4),
+        )
+        .unwrap();
+        assert_eq!(simulation_report.game_count, 4);
+        assert_eq!(simulation_report.stalled_count, 0);
+    }
+
+    // Without the consequence that ends it, the setup phase swaps cards
+    // forever in the first turn, so the turn limit never comes
+    #[test]
+    fn a_turn_that_never_ends_stalls() {
+        let setup_end = "            consequences:\n              - description: \"Next player's turn to swap cards\"\n                verb: EndPhase\n";
+        assert!(POO_HEAD_RULES_YAML.contains(setup_end));
+        let game_rules: GameRules =
+            serde_yaml::from_str(&POO_HEAD_RULES_YAML.replace(setup_end, "")).unwrap();
+
+        let simulation_report =
+            simulate(&game_rules, &seats(&["A:random", "B:random"]), &settings(2)).unwrap();
+        assert_eq!(simulation_report.stalled_count, 2);
+        assert!(simulation_report
+            .to_string()
+            .contains("or 2000 decisions: 2"));
+    }
+}
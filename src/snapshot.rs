@@ -0,0 +1,200 @@
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::game_rules::GameRules;
+use crate::game_state::GameState;
+
+// A saved game in progress. The game state carries its own copy of the rules,
+// and the fingerprint records which rules those were so a snapshot can't be
+// resumed against a rules file that has since changed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub rules_fingerprint: String,
+    pub game_state: GameState,
+}
+
+enum FileFormat {
+    Json,
+    Yaml,
+}
+
+impl FileFormat {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => FileFormat::Json,
+            _ => FileFormat::Yaml,
+        }
+    }
+}
+
+// Saved as JSON if the path ends in .json, otherwise as YAML
+pub fn save_file<T: Serialize>(value: &T, path: &Path) -> Result<(), String> {
+    let serialized = match FileFormat::from_path(path) {
+        FileFormat::Json => serde_json::to_string_pretty(value).map_err(|error| error.to_string()),
+        FileFormat::Yaml => serde_yaml::to_string(value).map_err(|error| error.to_string()),
+    }
+    .map_err(|error| format!("Couldn't serialize {}: {}", path.display(), error))?;
+
+    return fs::write(path, serialized)
+        .map_err(|error| format!("Couldn't write {}: {}", path.display(), error));
+}
+
+pub fn load_file<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let serialized = fs::read_to_string(path)
+        .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+
+    return match FileFormat::from_path(path) {
+        FileFormat::Json => serde_json::from_str(&serialized).map_err(|error| error.to_string()),
+        FileFormat::Yaml => serde_yaml::from_str(&serialized).map_err(|error| error.to_string()),
+    }
+    .map_err(|error| format!("Couldn't parse {}: {}", path.display(), error));
+}
+
+// A hash of the serialized value. 64 bit FNV-1a, which is stable across
+// platforms and Rust versions
+pub fn fingerprint<T: Serialize>(value: &T) -> String {
+    let serialized = serde_json::to_string(value).expect("Error serializing a fingerprinted value");
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in serialized.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    return format!("{:016x}", hash);
+}
+
+impl GameSnapshot {
+    pub fn new(game_state: &GameState) -> Self {
+        GameSnapshot {
+            rules_fingerprint: game_state.game_rules().fingerprint(),
+            game_state: game_state.clone(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        save_file(self, path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        load_file(path)
+    }
+
+    // Checks the snapshot against the rules it's being resumed with before
+    // handing back the game
+    pub fn into_game_state(self, game_rules: &GameRules) -> Result<GameState, String> {
+        if self.game_state.game_rules().fingerprint() != self.rules_fingerprint {
+            return Err("The rules saved in the snapshot don't match its rules fingerprint".into());
+        }
+
+        let game_rules_fingerprint = game_rules.fingerprint();
+        if game_rules_fingerprint != self.rules_fingerprint {
+            return Err(format!(
+                "The game was saved with different rules. Saved rules fingerprint: {}. Current rules fingerprint: {}",
+                self.rules_fingerprint, game_rules_fingerprint,
+            ));
+        }
+
+        return Ok(self.game_state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+    use crate::game_error::GameError;
+    use crate::game_rules::poo_head_rules;
+    use crate::player::Player;
+    use crate::player_controller::{bot_for, PlayerController};
+
+    fn random_bots(game_state: &GameState) -> Vec<Box<dyn PlayerController>> {
+        (0..game_state.players.len())
+            .map(|player_index| {
+                bot_for(
+                    game_state.players[player_index].controller,
+                    player_index,
+                    game_state.seed,
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    // A seeded game between random bots, stopped part way through a turn
+    fn game_in_progress() -> GameState {
+        let players = ["Ann:random", "Bob:random", "Cat:random"]
+            .iter()
+            .map(|player_spec| Player::from_spec(player_spec).unwrap())
+            .collect();
+        let mut game_state = GameState::new(poo_head_rules(), players, 5);
+        game_state.deal();
+        game_state.decision_limit = Some(40);
+
+        let mut controllers = random_bots(&game_state);
+        assert_eq!(
+            game_state.play_game(&mut controllers).unwrap_err(),
+            GameError::DecisionLimitReached(40)
+        );
+        game_state.decision_limit = None;
+
+        return game_state;
+    }
+
+    #[test]
+    fn resumed_games_play_on_as_if_never_saved() {
+        for extension in ["yaml", "json"] {
+            let mut game_state = game_in_progress();
+            let snapshot_path = env::temp_dir().join(format!(
+                "card-game-snapshot-{}.{}",
+                process::id(),
+                extension
+            ));
+
+            GameSnapshot::new(&game_state).save(&snapshot_path).unwrap();
+            let snapshot = GameSnapshot::load(&snapshot_path);
+            fs::remove_file(&snapshot_path).unwrap();
+            let mut resumed_state = snapshot
+                .unwrap()
+                .into_game_state(&poo_head_rules())
+                .unwrap();
+            assert_eq!(resumed_state.fingerprint(), game_state.fingerprint());
+
+            let mut controllers = random_bots(&game_state);
+            let game_result = game_state.play_game(&mut controllers).unwrap();
+            let mut controllers = random_bots(&resumed_state);
+            let resumed_result = resumed_state.play_game(&mut controllers).unwrap();
+
+            assert_eq!(resumed_result, game_result);
+            assert_eq!(resumed_state.fingerprint(), game_state.fingerprint());
+        }
+    }
+
+    #[test]
+    fn snapshots_are_only_resumed_with_the_rules_they_were_saved_with() {
+        let snapshot = GameSnapshot::new(&game_in_progress());
+        let mut changed_rules = poo_head_rules();
+        changed_rules.max_player_count = 5;
+
+        let error = snapshot.into_game_state(&changed_rules).unwrap_err();
+        assert!(error.starts_with("The game was saved with different rules"));
+    }
+
+    // Eg. someone edited the rules in the snapshot file by hand
+    #[test]
+    fn snapshots_whose_rules_dont_match_their_fingerprint_are_turned_down() {
+        let mut snapshot = GameSnapshot::new(&game_in_progress());
+        snapshot.rules_fingerprint = "0123456789abcdef".into();
+
+        let error = snapshot.into_game_state(&poo_head_rules()).unwrap_err();
+        assert_eq!(
+            error,
+            "The rules saved in the snapshot don't match its rules fingerprint"
+        );
+    }
+}
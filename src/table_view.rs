@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::card_deck::{self, Card, CardGroup};
+use crate::game_state::GameState;
+
+// What a player can see of each card group, by name. Hidden cards are None
+type CardGroupViews = BTreeMap<String, Vec<Option<Card>>>;
+
+fn card_group_views(
+    card_groups: &BTreeMap<String, CardGroup>,
+    seen_by_owner: bool,
+) -> CardGroupViews {
+    return card_groups
+        .iter()
+        .map(|(name, card_group)| {
+            let visible_cards = card_group
+                .visible_cards(seen_by_owner)
+                .into_iter()
+                .map(|visible_card| visible_card.cloned())
+                .collect();
+            (name.clone(), visible_cards)
+        })
+        .collect();
+}
+
+fn view_card_groups(card_group_views: &CardGroupViews) -> String {
+    return card_group_views
+        .iter()
+        .map(|(name, visible_cards)| {
+            format!(
+                "{}: [{}]",
+                name,
+                card_deck::view_cards(visible_cards.iter().map(Option::as_ref))
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerView {
+    pub name: String,
+    pub card_groups: CardGroupViews,
+    pub finished: bool,
+}
+
+// The table as one player sees it, without any card they couldn't see, so it
+// can be sent to a player on another computer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableView {
+    pub viewer_index: usize,
+    pub current_player_index: usize,
+    // None between turns
+    pub turn_phase: Option<String>,
+    pub communal_cards: CardGroupViews,
+    // Every player in seating order, including the viewer
+    pub players: Vec<PlayerView>,
+}
+
+impl TableView {
+    pub fn new(game_state: &GameState, viewer_index: usize) -> Self {
+        let turn_phase = game_state
+            .game_rules()
+            .turn_phases(game_state.turn_type_index)
+            .get(game_state.turn_phase_index)
+            .map(|turn_phase| turn_phase.name.clone());
+
+        let players = game_state
+            .players
+            .iter()
+            .enumerate()
+            .map(|(player_index, player)| PlayerView {
+                name: player.name.clone(),
+                card_groups: card_group_views(&player.hand, player_index == viewer_index),
+                finished: game_state.player_finished(player_index),
+            })
+            .collect();
+
+        return TableView {
+            viewer_index,
+            current_player_index: game_state.player_index_at_offset(0),
+            turn_phase,
+            communal_cards: card_group_views(&game_state.communal_cards, false),
+            players,
+        };
+    }
+}
+
+// Communal cards first, then everyone else's cards in seating order after the
+// viewer, then the viewer's own
+impl fmt::Display for TableView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(turn_phase) = &self.turn_phase {
+            writeln!(
+                f,
+                "{}'s turn. Turn phase: {}",
+                self.players[self.current_player_index].name, turn_phase
+            )?;
+        }
+
+        write!(
+            f,
+            "Communal cards: {}",
+            view_card_groups(&self.communal_cards)
+        )?;
+
+        let player_count = self.players.len();
+        for offset in 1..player_count {
+            let player = &self.players[(self.viewer_index + offset) % player_count];
+            write!(
+                f,
+                "\n{}'s cards: {}",
+                player.name,
+                view_card_groups(&player.card_groups)
+            )?;
+        }
+
+        write!(
+            f,
+            "\nYour cards: {}",
+            view_card_groups(&self.players[self.viewer_index].card_groups)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_rules::poo_head_rules;
+    use crate::player::Player;
+
+    fn dealt_game() -> GameState {
+        let players = vec![
+            Player::from_spec("Ann").unwrap(),
+            Player::from_spec("Bob").unwrap(),
+        ];
+        let mut game_state = GameState::new(poo_head_rules(), players, 0);
+        game_state.deal();
+
+        return game_state;
+    }
+
+    fn actual_cards(game_state: &GameState, player_index: usize, name: &str) -> Vec<Option<Card>> {
+        game_state.players[player_index].hand[name]
+            .cards
+            .iter()
+            .cloned()
+            .map(Some)
+            .collect()
+    }
+
+    #[test]
+    fn players_see_their_own_hand_but_not_anyone_elses() {
+        let game_state = dealt_game();
+        let table_view = TableView::new(&game_state, 0);
+        let ann = &table_view.players[0].card_groups;
+        let bob = &table_view.players[1].card_groups;
+
+        assert_eq!(
+            ann["hand_cards"],
+            actual_cards(&game_state, 0, "hand_cards")
+        );
+        assert_eq!(bob["hand_cards"], vec![None; 3]);
+    }
+
+    #[test]
+    fn nobody_sees_blind_cards_and_everyone_sees_lower_cards() {
+        let game_state = dealt_game();
+        let table_view = TableView::new(&game_state, 0);
+
+        for (player_index, player_view) in table_view.players.iter().enumerate() {
+            assert_eq!(player_view.card_groups["blind_cards"], vec![None; 3]);
+            assert_eq!(
+                player_view.card_groups["lower_cards"],
+                actual_cards(&game_state, player_index, "lower_cards")
+            );
+        }
+    }
+
+    #[test]
+    fn hidden_cards_are_shown_as_card_backs() {
+        let game_state = dealt_game();
+        let table_view = TableView::new(&game_state, 1).to_string();
+        let card_backs = card_deck::view_cards([None; 3]);
+
+        let ann_line = table_view
+            .lines()
+            .find(|line| line.starts_with("Ann's cards:"))
+            .unwrap();
+        assert!(ann_line.contains(&format!("blind_cards: [{}]", card_backs)));
+        assert!(ann_line.contains(&format!("hand_cards: [{}]", card_backs)));
+
+        let your_line = table_view
+            .lines()
+            .find(|line| line.starts_with("Your cards:"))
+            .unwrap();
+        assert!(your_line.contains(&format!("blind_cards: [{}]", card_backs)));
+        assert!(!your_line.contains(&format!("hand_cards: [{}]", card_backs)));
+    }
+}
@@ -1,29 +1,245 @@
-use crate::game_rules::Action;
-use text_io::read;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::str::FromStr;
 
-pub fn select_action(available_actions: Vec<&Action>) -> Option<&Action> {
-    if available_actions.len() > 1 {
+use crate::game_error::{GameError, InputError};
+use crate::game_log::LogEntry;
+use crate::game_state::GameState;
+use crate::player_controller::{ActionOption, CardChoice, PlayerController};
+use crate::table_view::TableView;
+use text_io::try_read;
+
+// Reads the next whitespace separated word from the terminal
+fn read_word() -> Result<String, InputError> {
+    // An empty word means stdin has been closed
+    let input: Result<String, _> = try_read!();
+    match input {
+        Ok(input) if !input.is_empty() => return Ok(input),
+        _ => return Err(InputError::EndOfInput),
+    }
+}
+
+// Reads a number from the terminal, asking again until it gets one
+fn read_index<T: FromStr>() -> Result<T, InputError> {
+    loop {
+        let input = read_word()?;
+        match input.parse::<T>() {
+            Ok(index) => return Ok(index),
+            Err(_) => println!("Please enter a number. Given: {}", input),
+        }
+    }
+}
+
+const CLEAR_SCREEN: &str = "\x1B[2J\x1B[1;1H";
+
+// Hides the last player's cards and waits for the next player to take over the
+// terminal
+fn hand_off(player_name: &str) -> Result<(), InputError> {
+    print!("{}", CLEAR_SCREEN);
+    print!("Pass to {}, press enter", player_name);
+    io::stdout().flush().map_err(|_| InputError::EndOfInput)?;
+
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => return Err(InputError::EndOfInput),
+        Ok(_) => {}
+    }
+
+    print!("{}", CLEAR_SCREEN);
+    return Ok(());
+}
+
+// Asks how many people are playing, within the game's limits, and their names
+pub fn read_player_names(
+    min_player_count: usize,
+    max_player_count: usize,
+) -> Result<Vec<String>, InputError> {
+    let player_count = loop {
         println!(
-            "Select action by number:\n{}",
-            available_actions
-                .iter()
-                .enumerate()
-                .map(|(i, a)| format!("{}. {}", i, a))
-                .collect::<Vec<String>>()
-                .join("\n"),
+            "How many players? Use {}-{}:",
+            min_player_count, max_player_count
         );
-        let selected_action_index: usize = read!();
-        if let Some(chosen_action) = available_actions.get(selected_action_index) {
-            return Some(chosen_action);
+        let player_count: usize = read_index()?;
+        if (min_player_count..=max_player_count).contains(&player_count) {
+            break player_count;
+        }
+
+        println!("Invalid player count: {}", player_count);
+    };
+
+    let mut player_names = vec![];
+    for player_number in 1..=player_count {
+        println!(
+            "Player {} name, or NAME:random, NAME:heuristic or NAME:mcts for a bot:",
+            player_number
+        );
+        player_names.push(read_word()?);
+    }
+
+    return Ok(player_names);
+}
+
+// Lists the options and reads the number of the one picked. The game checks
+// the number
+pub fn read_action_choice(options: &[String]) -> Result<usize, InputError> {
+    println!(
+        "Select action by number:\n{}",
+        options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| format!("{}. {}", i, option))
+            .collect::<Vec<String>>()
+            .join("\n"),
+    );
+
+    return read_index();
+}
+
+// Reads cards to move a card at a time until the player finishes. The game
+// checks the cards can be moved together
+pub fn read_card_selection(
+    source: &str,
+    destination: &str,
+    card_count: usize,
+) -> Result<Vec<usize>, InputError> {
+    if card_count == 0 {
+        println!("No cards to move from {}", source);
+        return Ok(vec![]);
+    }
+
+    let mut selected_card_indices: Vec<usize> = vec![];
+    loop {
+        println!(
+            "Select card to move from {} into {}. Use 0-{} or -1 to finish:",
+            source,
+            destination,
+            card_count - 1,
+        );
+
+        let selected_card_index: isize = read_index()?;
+        if selected_card_index < 0 {
+            return Ok(selected_card_indices);
+        }
+
+        let selected_card_index = selected_card_index as usize;
+        if selected_card_index >= card_count {
+            println!("Invalid card index: {}", selected_card_index);
+        } else if selected_card_indices.contains(&selected_card_index) {
+            println!("Card {} is already selected", selected_card_index);
         } else {
-            println!("Invalid action selection");
-            return select_action(available_actions);
-        }
-    } else if available_actions.len() == 1 {
-        println!("{}", available_actions[0]);
-        return Some(available_actions[0]);
-    } else {
-        println!("No actions available to you right now");
-        return None;
+            selected_card_indices.push(selected_card_index);
+        }
+    }
+}
+
+// What every seat played from this terminal shares
+pub struct Terminal {
+    hot_seat: bool,
+    player_at_terminal: Option<usize>,
+    shown_log_length: usize,
+    shown_table_view: Option<TableView>,
+}
+
+impl Terminal {
+    pub fn new(hot_seat: bool) -> Rc<RefCell<Terminal>> {
+        Rc::new(RefCell::new(Terminal {
+            hot_seat,
+            player_at_terminal: None,
+            shown_log_length: 0,
+            shown_table_view: None,
+        }))
+    }
+}
+
+// A person playing a seat from the terminal. Players sharing the terminal see
+// each other's choices unless it's a hot seat game, where the screen is
+// cleared and handed over between players
+pub struct TerminalController {
+    terminal: Rc<RefCell<Terminal>>,
+    // How much of the game log this seat has been shown, so a hot seat player
+    // sees what happened since their last turn
+    seen_log_length: usize,
+}
+
+impl TerminalController {
+    pub fn new(terminal: &Rc<RefCell<Terminal>>) -> Self {
+        TerminalController {
+            terminal: Rc::clone(terminal),
+            seen_log_length: 0,
+        }
+    }
+
+    // Shows what happened since the player last looked, and the table if it
+    // changed
+    fn catch_up(&mut self, game_state: &GameState, player_index: usize) -> Result<(), InputError> {
+        let mut terminal = self.terminal.borrow_mut();
+        if terminal.hot_seat && terminal.player_at_terminal != Some(player_index) {
+            hand_off(&game_state.players[player_index].name)?;
+            terminal.player_at_terminal = Some(player_index);
+            terminal.shown_log_length = self.seen_log_length;
+            terminal.shown_table_view = None;
+        }
+
+        let log_entries = &game_state.game_log.entries;
+        for log_entry in log_entries[terminal.shown_log_length.min(log_entries.len())..].iter() {
+            if let LogEntry::ActionExecuted {
+                player_index,
+                description,
+            } = log_entry
+            {
+                println!(
+                    "{}: {}",
+                    game_state.players[*player_index].name, description
+                );
+            }
+        }
+        terminal.shown_log_length = log_entries.len();
+        self.seen_log_length = log_entries.len();
+
+        let table_view = TableView::new(game_state, player_index);
+        if terminal.shown_table_view.as_ref() != Some(&table_view) {
+            println!();
+            println!("{}", table_view);
+            terminal.shown_table_view = Some(table_view);
+        }
+
+        return Ok(());
+    }
+}
+
+impl PlayerController for TerminalController {
+    fn choose_action(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        options: &[ActionOption],
+    ) -> Result<usize, GameError> {
+        self.catch_up(game_state, player_index)?;
+
+        let options = options
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>();
+        return Ok(read_action_choice(&options)?);
+    }
+
+    fn choose_cards(
+        &mut self,
+        game_state: &GameState,
+        player_index: usize,
+        card_choice: &CardChoice,
+    ) -> Result<Vec<usize>, GameError> {
+        self.catch_up(game_state, player_index)?;
+
+        return Ok(read_card_selection(
+            &card_choice.source,
+            &card_choice.destination,
+            card_choice.card_count(),
+        )?);
+    }
+
+    fn show_message(&mut self, _game_state: &GameState, _player_index: usize, message: &str) {
+        println!("{}", message);
     }
 }